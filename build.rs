@@ -0,0 +1,137 @@
+//! Records how this binary was built, for `src/environment.rs` to report
+//! next to every result.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("build_info.rs");
+    let manifest = fs::read_to_string(Path::new(&manifest_dir).join("Cargo.toml")).unwrap();
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    let rustc_version = command(&rustc, &["-V"], Path::new(&manifest_dir));
+    let profile = format!(
+        "{} (opt-level {}, debug {})",
+        env::var("PROFILE").unwrap_or_default(),
+        env::var("OPT_LEVEL").unwrap_or_default(),
+        env::var("DEBUG").unwrap_or_default()
+    );
+
+    let mut deps = vec![];
+    for (name, path) in path_deps(&manifest) {
+        let dir = Path::new(&manifest_dir).join(&path);
+        let dir = dir.canonicalize().unwrap_or(dir);
+        let version = fs::read_to_string(dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|toml| package_version(&toml))
+            .unwrap_or_else(|| String::from("unknown"));
+        println!(
+            "cargo:rerun-if-changed={}",
+            dir.join("Cargo.toml").display()
+        );
+        watch_git(&dir);
+        deps.push((name, version, dir.display().to_string(), git_rev(&dir)));
+    }
+
+    let mut info = String::new();
+    info += &format!(
+        "pub const GIT_REV: &str = {:?};\n",
+        git_rev(Path::new(&manifest_dir))
+    );
+    info += &format!("pub const RUSTC: &str = {:?};\n", rustc_version);
+    info += &format!("pub const PROFILE: &str = {:?};\n", profile);
+    info += &format!(
+        "pub const TARGET: &str = {:?};\n",
+        env::var("TARGET").unwrap_or_default()
+    );
+    info += &format!(
+        "pub const TARGET_FEATURES: &str = {:?};\n",
+        env::var("CARGO_CFG_TARGET_FEATURE").unwrap_or_default()
+    );
+    info += "/// `(name, version, path, git revision)` of each path dependency.\n";
+    info += "pub const PATH_DEPS: &[(&str, &str, &str, &str)] = &[\n";
+    for (name, version, path, rev) in deps {
+        info += &format!("    ({:?}, {:?}, {:?}, {:?}),\n", name, version, path, rev);
+    }
+    info += "];\n";
+    fs::write(out, info).unwrap();
+
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    watch_git(Path::new(&manifest_dir));
+}
+
+/// `name = {path = "..."}` lines of the manifest, skipping commented ones.
+fn path_deps(manifest: &str) -> Vec<(String, String)> {
+    manifest
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (name, rest) = line.split_at(line.find('=')?);
+            let path = rest.split("path = \"").nth(1)?.split('"').next()?;
+            Some((name.trim().to_string(), path.to_string()))
+        })
+        .collect()
+}
+
+/// The `version` of a manifest's `[package]`, which comes before any other
+/// table in every manifest we depend on.
+fn package_version(manifest: &str) -> Option<String> {
+    manifest
+        .lines()
+        .take_while(|line| !line.starts_with('[') || line.trim() == "[package]")
+        .find(|line| line.trim_start().starts_with("version"))
+        .and_then(|line| line.split('"').nth(1))
+        .map(String::from)
+}
+
+fn git_rev(dir: &Path) -> String {
+    command("git", &["describe", "--always", "--dirty"], dir)
+}
+
+/// Have cargo run this again whenever `dir`'s revision or dirty state may
+/// have moved: a checkout changes HEAD, a commit the branch it points to,
+/// loose or packed, staging the index, and an edit `src`. Edits to tracked
+/// files outside `src` still only show up on the next rebuild.
+fn watch_git(dir: &Path) {
+    let mut paths = vec![
+        String::from("HEAD"),
+        String::from("index"),
+        String::from("packed-refs"),
+    ];
+    let branch = command("git", &["symbolic-ref", "-q", "HEAD"], dir);
+    if branch != "unknown" {
+        paths.push(branch);
+    }
+    let mut watched: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| command("git", &["rev-parse", "--git-path", path], dir))
+        .filter(|path| path != "unknown")
+        .map(|path| dir.join(path))
+        .collect();
+    watched.push(dir.join("src"));
+    // Cargo treats a missing path as always changed
+    for path in watched.iter().filter(|path| path.exists()) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
+
+/// First line of what `program` prints, or `unknown` if it couldn't run.
+fn command(program: &str, args: &[&str], dir: &Path) -> String {
+    Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .next()
+                .map(String::from)
+        })
+        .unwrap_or_else(|| String::from("unknown"))
+}
@@ -0,0 +1,434 @@
+use crate::{binning, Iv};
+use ailist::{self};
+use bio::data_structures::interval_tree::IntervalTree;
+use bio::utils::Interval as BioInterval;
+use coitree::{COITree, IntervalNode};
+use crossbeam_utils::thread;
+use iproxy::{self};
+use nested_intervals::IntervalSet;
+use rust_hopper::{self};
+use rust_lapper::{Interval, Lapper};
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::ops::Range;
+use std::slice;
+
+/// Settings for the libraries that take any, from the command line. Every
+/// build is handed them, and libraries without settings ignore them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Config {
+    pub binning: binning::Scheme,
+}
+
+/// The common surface every library is driven through. Conversion from `Iv`
+/// into the library's own interval type is kept out of `build` so the two
+/// can be timed as separate phases.
+pub trait Adapter: Sized {
+    /// Prefix for every line of output about this library.
+    const NAME: &'static str;
+    /// Whether the library can count overlaps without iterating them.
+    const HAS_COUNT: bool = false;
+    /// Whether `insert` updates the index in place. Libraries without it
+    /// are rebuilt from the live set by the dynamic workload instead.
+    const HAS_INSERT: bool = false;
+    /// Whether `remove` updates the index in place, as for `HAS_INSERT`.
+    const HAS_REMOVE: bool = false;
+    /// Whether the library can resume a search from a cursor left by the
+    /// previous query, for queries sorted by start.
+    const HAS_SEEK: bool = false;
+    /// Whether the library can find the nearest interval to a position.
+    /// None of the current ones can; `--list_libs` says so.
+    const HAS_NEAREST: bool = false;
+    /// Coordinate type the library is driven with. `Iv`'s `u32`s are cast
+    /// into it, so it bounds the largest position that works.
+    const COORD: &'static str = "u32";
+    type Item;
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item>;
+    fn build(items: Vec<Self::Item>, config: &Config) -> Self;
+    /// As `build`, for libraries that can reject a set. The run skips them
+    /// on it rather than aborting.
+    fn try_build(items: Vec<Self::Item>, config: &Config) -> Result<Self, String> {
+        Ok(Self::build(items, config))
+    }
+    /// Number of intervals overlapping `start..stop`, found by iterating.
+    fn find(&self, start: u32, stop: u32) -> usize;
+    /// Only timed when `HAS_COUNT` is set.
+    fn count(&self, start: u32, stop: u32) -> usize {
+        self.find(start, stop)
+    }
+
+    /// As `find`, starting from `cursor` and leaving it where the next,
+    /// later starting query should begin. Only called when `HAS_SEEK` is set.
+    fn seek(&self, _start: u32, _stop: u32, _cursor: &mut usize) -> usize {
+        unreachable!("{} can't seek", Self::NAME)
+    }
+
+    /// Only called when `HAS_INSERT` is set.
+    fn insert(&mut self, _item: Self::Item) {
+        unreachable!("{} can't insert in place", Self::NAME)
+    }
+
+    /// Remove one interval spanning exactly `start..stop`, returning whether
+    /// there was one. Only called when `HAS_REMOVE` is set.
+    fn remove(&mut self, _start: u32, _stop: u32) -> bool {
+        unreachable!("{} can't remove in place", Self::NAME)
+    }
+}
+
+/// One index per contig. Queries are routed by the contig id on the `Iv`.
+/// The config it was built with is kept for rebuilding contigs later.
+#[derive(Clone)]
+pub struct ContigIndex<A> {
+    indices: Vec<Option<A>>,
+    config: Config,
+}
+
+impl<A: Adapter> ContigIndex<A> {
+    /// Group `ivs` by contig and convert each group, ready for `build`.
+    pub fn convert(ivs: &[Iv], num_contigs: usize) -> Vec<Vec<A::Item>> {
+        split_by_contig(ivs, num_contigs)
+            .iter()
+            .map(|ivs| A::convert(ivs))
+            .collect()
+    }
+
+    pub fn build(items: Vec<Vec<A::Item>>, config: &Config) -> Self {
+        Self::try_build(items, config).unwrap_or_else(|e| panic!("{}: {}", A::NAME, e))
+    }
+
+    /// `build`, or why the library rejected one of the contigs.
+    pub fn try_build(items: Vec<Vec<A::Item>>, config: &Config) -> Result<Self, String> {
+        let indices = items
+            .into_iter()
+            .map(|items| try_build_contig(items, config))
+            .collect::<Result<_, _>>()?;
+        Ok(ContigIndex {
+            indices,
+            config: *config,
+        })
+    }
+
+    /// `build` across `threads` threads. Contigs are handed out biggest
+    /// first, each to the thread with the fewest intervals so far.
+    pub fn build_parallel(items: Vec<Vec<A::Item>>, threads: usize, config: &Config) -> Self
+    where
+        A: Send,
+        A::Item: Send,
+    {
+        let mut indices: Vec<Option<A>> = items.iter().map(|_| None).collect();
+        let mut contigs: Vec<(usize, Vec<A::Item>)> = items.into_iter().enumerate().collect();
+        contigs.sort_by_key(|(_, items)| Reverse(items.len()));
+        let mut loads = vec![0; threads];
+        let mut buckets: Vec<Vec<(usize, Vec<A::Item>)>> = (0..threads).map(|_| vec![]).collect();
+        for (contig, items) in contigs {
+            let least = (0..threads).min_by_key(|&t| loads[t]).unwrap();
+            loads[least] += items.len();
+            buckets[least].push((contig, items));
+        }
+        thread::scope(|s| {
+            let handles: Vec<_> = buckets
+                .into_iter()
+                .map(|bucket| {
+                    s.spawn(move |_| {
+                        bucket
+                            .into_iter()
+                            .map(|(contig, items)| (contig, build_contig(items, config)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            for handle in handles {
+                for (contig, index) in handle.join().unwrap() {
+                    indices[contig] = index;
+                }
+            }
+        })
+        .unwrap();
+        ContigIndex {
+            indices,
+            config: *config,
+        }
+    }
+
+    pub fn find(&self, iv: &Iv) -> usize {
+        match self.indices.get(iv.contig as usize) {
+            Some(Some(index)) => index.find(iv.start, iv.stop),
+            _ => 0,
+        }
+    }
+
+    pub fn count(&self, iv: &Iv) -> usize {
+        match self.indices.get(iv.contig as usize) {
+            Some(Some(index)) => index.count(iv.start, iv.stop),
+            _ => 0,
+        }
+    }
+
+    /// `Adapter::seek` in the query's contig. Callers reset `cursor` when
+    /// the contig changes.
+    pub fn seek(&self, iv: &Iv, cursor: &mut usize) -> usize {
+        match self.indices.get(iv.contig as usize) {
+            Some(Some(index)) => index.seek(iv.start, iv.stop, cursor),
+            _ => 0,
+        }
+    }
+
+    /// Insert in place, or build the contig's first index from `iv` alone.
+    pub fn insert(&mut self, iv: &Iv) {
+        let item = A::convert(slice::from_ref(iv)).pop().unwrap();
+        match self.indices[iv.contig as usize] {
+            Some(ref mut index) => index.insert(item),
+            ref mut empty => *empty = Some(A::build(vec![item], &self.config)),
+        }
+    }
+
+    pub fn remove(&mut self, iv: &Iv) -> bool {
+        match self.indices[iv.contig as usize] {
+            Some(ref mut index) => index.remove(iv.start, iv.stop),
+            None => false,
+        }
+    }
+
+    /// Throw away one contig's index and build it again from `ivs`.
+    pub fn rebuild(&mut self, contig: usize, ivs: &[Iv]) {
+        self.indices[contig] = build_contig(A::convert(ivs), &self.config);
+    }
+}
+
+/// Contigs without intervals get no index, since not every library
+/// accepts an empty set.
+fn build_contig<A: Adapter>(items: Vec<A::Item>, config: &Config) -> Option<A> {
+    try_build_contig(items, config).unwrap_or_else(|e| panic!("{}: {}", A::NAME, e))
+}
+
+fn try_build_contig<A: Adapter>(items: Vec<A::Item>, config: &Config) -> Result<Option<A>, String> {
+    if items.is_empty() {
+        Ok(None)
+    } else {
+        A::try_build(items, config).map(Some)
+    }
+}
+
+/// Object safe view of a built `ContigIndex`, so indices from several
+/// libraries can be held side by side and fed the same queries.
+pub trait Index {
+    fn name(&self) -> &'static str;
+    /// Total overlaps found for every query in `queries`.
+    fn find_all(&self, queries: &[Iv]) -> usize;
+}
+
+impl<A: Adapter> Index for ContigIndex<A> {
+    fn name(&self) -> &'static str {
+        A::NAME
+    }
+
+    fn find_all(&self, queries: &[Iv]) -> usize {
+        queries.iter().map(|iv| self.find(iv)).sum()
+    }
+}
+
+pub fn split_by_contig(ivs: &[Iv], num_contigs: usize) -> Vec<Vec<Iv>> {
+    let mut result: Vec<Vec<Iv>> = (0..num_contigs).map(|_| vec![]).collect();
+    for iv in ivs {
+        result[iv.contig as usize].push(*iv);
+    }
+    result
+}
+
+pub struct RustLapper(Lapper<u32>);
+
+impl Adapter for RustLapper {
+    const NAME: &'static str = "rust-lapper";
+    const HAS_COUNT: bool = true;
+    const HAS_SEEK: bool = true;
+    type Item = Interval<u32>;
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item> {
+        ivs.iter()
+            .map(|i| Interval {
+                start: i.start,
+                stop: i.stop,
+                val: 0,
+            })
+            .collect()
+    }
+
+    fn build(items: Vec<Self::Item>, _config: &Config) -> Self {
+        RustLapper(Lapper::new(items))
+    }
+
+    fn find(&self, start: u32, stop: u32) -> usize {
+        self.0.find(start, stop).count()
+    }
+
+    fn count(&self, start: u32, stop: u32) -> usize {
+        self.0.count(start, stop)
+    }
+
+    fn seek(&self, start: u32, stop: u32, cursor: &mut usize) -> usize {
+        self.0.seek(start, stop, cursor).count()
+    }
+}
+
+pub struct RustHopper(rust_hopper::Hopper<u32>);
+
+impl Adapter for RustHopper {
+    const NAME: &'static str = "rust-hopper";
+    const COORD: &'static str = "usize";
+    type Item = rust_hopper::Interval<u32>;
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item> {
+        ivs.iter()
+            .map(|i| rust_hopper::Interval {
+                start: i.start as usize,
+                stop: i.stop as usize,
+                val: 0,
+            })
+            .collect()
+    }
+
+    fn build(items: Vec<Self::Item>, _config: &Config) -> Self {
+        RustHopper(rust_hopper::Hopper::new(items))
+    }
+
+    fn find(&self, start: u32, stop: u32) -> usize {
+        self.0.find(start as usize, stop as usize).count()
+    }
+}
+
+pub struct IProxy(iproxy::IProxy<u32>);
+
+impl Adapter for IProxy {
+    const NAME: &'static str = "IProxy";
+    type Item = iproxy::Interval<u32>;
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item> {
+        ivs.iter()
+            .map(|i| iproxy::Interval {
+                start: i.start,
+                stop: i.stop,
+                val: 0,
+            })
+            .collect()
+    }
+
+    fn build(items: Vec<Self::Item>, _config: &Config) -> Self {
+        IProxy(iproxy::IProxy::new(items))
+    }
+
+    fn find(&self, start: u32, stop: u32) -> usize {
+        self.0.find(start, stop).count()
+    }
+}
+
+pub struct AIList(ailist::AIList<u32>);
+
+impl Adapter for AIList {
+    const NAME: &'static str = "AIList";
+    type Item = ailist::Interval<u32>;
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item> {
+        ivs.iter()
+            .map(|i| ailist::Interval {
+                start: i.start,
+                end: i.stop,
+                val: 0,
+            })
+            .collect()
+    }
+
+    fn build(items: Vec<Self::Item>, _config: &Config) -> Self {
+        AIList(ailist::AIList::new(items, None))
+    }
+
+    fn find(&self, start: u32, stop: u32) -> usize {
+        self.0.find(start, stop).count()
+    }
+}
+
+pub struct CoiTree(COITree<u32>);
+
+impl Adapter for CoiTree {
+    const NAME: &'static str = "COITree";
+    const COORD: &'static str = "i32";
+    type Item = IntervalNode<u32>;
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item> {
+        ivs.iter()
+            .map(|i| IntervalNode::new(i.start as i32, i.stop as i32, 0))
+            .collect()
+    }
+
+    fn build(items: Vec<Self::Item>, _config: &Config) -> Self {
+        CoiTree(COITree::new(items))
+    }
+
+    fn find(&self, start: u32, stop: u32) -> usize {
+        self.0.find(start as i32, stop as i32).count()
+    }
+}
+
+/// rust-bio's tree is built by repeated `insert`, so the whole insert loop
+/// is what gets timed as construction. Ranges are validated into rust-bio's
+/// own `Interval` up front, otherwise `insert` would do it inside the build.
+/// The tree has no delete, so removals go through a rebuild.
+pub struct RustBio(IntervalTree<u32, u32>);
+
+impl Adapter for RustBio {
+    const NAME: &'static str = "rust-bio";
+    const HAS_INSERT: bool = true;
+    type Item = BioInterval<u32>;
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item> {
+        ivs.iter()
+            .map(|x| BioInterval::from(x.start..x.stop))
+            .collect()
+    }
+
+    fn build(items: Vec<Self::Item>, _config: &Config) -> Self {
+        let mut tree = IntervalTree::new();
+        items.into_iter().for_each(|x| tree.insert(x, 0));
+        RustBio(tree)
+    }
+
+    fn find(&self, start: u32, stop: u32) -> usize {
+        self.0.find(start..stop).count()
+    }
+
+    fn insert(&mut self, item: Self::Item) {
+        self.0.insert(item, 0);
+    }
+}
+
+// Cons: querys require mutability
+// Cons: no way to tag an interval
+#[derive(Clone)]
+pub struct NestedIntervals(RefCell<IntervalSet>);
+
+impl Adapter for NestedIntervals {
+    const NAME: &'static str = "nested-intervals";
+    type Item = Range<u32>;
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item> {
+        ivs.iter().map(|x| x.start..x.stop).collect()
+    }
+
+    fn build(items: Vec<Self::Item>, config: &Config) -> Self {
+        Self::try_build(items, config).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_build(items: Vec<Self::Item>, _config: &Config) -> Result<Self, String> {
+        IntervalSet::new(&items)
+            .map(|set| NestedIntervals(RefCell::new(set)))
+            .map_err(|e| format!("rejected the set: {:?}", e))
+    }
+
+    fn find(&self, start: u32, stop: u32) -> usize {
+        self.0
+            .borrow_mut()
+            .query_overlapping(&(start..stop))
+            .iter()
+            .count()
+    }
+}
@@ -0,0 +1,133 @@
+use crate::adapters::{Adapter, Config};
+use crate::Iv;
+
+/// Overlap test every structure in the bakeoff is held to: half open
+/// intervals that share at least one position.
+pub fn overlaps(iv: &Iv, start: u32, stop: u32) -> bool {
+    iv.start < stop && iv.stop > start
+}
+
+/// Brute force scan over every interval. Slow, but obviously correct, so it
+/// doubles as the oracle that other structures are checked against.
+pub struct Naive(Vec<Iv>);
+
+impl Adapter for Naive {
+    const NAME: &'static str = "naive";
+    const HAS_INSERT: bool = true;
+    const HAS_REMOVE: bool = true;
+    type Item = Iv;
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item> {
+        ivs.to_vec()
+    }
+
+    fn build(items: Vec<Self::Item>, _config: &Config) -> Self {
+        Naive(items)
+    }
+
+    fn find(&self, start: u32, stop: u32) -> usize {
+        self.0.iter().filter(|iv| overlaps(iv, start, stop)).count()
+    }
+
+    fn insert(&mut self, item: Self::Item) {
+        self.0.push(item);
+    }
+
+    fn remove(&mut self, start: u32, stop: u32) -> bool {
+        match self
+            .0
+            .iter()
+            .position(|iv| iv.start == start && iv.stop == stop)
+        {
+            Some(i) => {
+                self.0.swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Intervals sorted by start. A query binary searches for the first start
+/// that could still reach it, given the longest interval in the set, and
+/// scans forward until starts pass the end of the query. Updates shift the
+/// array, so they are linear, but need no rebuild.
+pub struct BinarySearch {
+    ivs: Vec<Iv>,
+    max_len: u32,
+}
+
+impl Adapter for BinarySearch {
+    const NAME: &'static str = "binary-search";
+    const HAS_INSERT: bool = true;
+    const HAS_REMOVE: bool = true;
+    type Item = Iv;
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item> {
+        ivs.to_vec()
+    }
+
+    fn build(mut items: Vec<Self::Item>, _config: &Config) -> Self {
+        items.sort_unstable_by_key(|iv| (iv.start, iv.stop));
+        let max_len = items
+            .iter()
+            .map(|iv| iv.stop.saturating_sub(iv.start))
+            .max()
+            .unwrap_or(0);
+        BinarySearch {
+            ivs: items,
+            max_len,
+        }
+    }
+
+    fn find(&self, start: u32, stop: u32) -> usize {
+        let lower = start.saturating_sub(self.max_len);
+        let first = lower_bound(&self.ivs, lower);
+        self.ivs[first..]
+            .iter()
+            .take_while(|iv| iv.start < stop)
+            .filter(|iv| iv.stop > start)
+            .count()
+    }
+
+    fn insert(&mut self, item: Self::Item) {
+        let i = lower_bound(&self.ivs, item.start);
+        let i = i + self.ivs[i..]
+            .iter()
+            .take_while(|iv| iv.start == item.start && iv.stop < item.stop)
+            .count();
+        self.max_len = self.max_len.max(item.stop.saturating_sub(item.start));
+        self.ivs.insert(i, item);
+    }
+
+    // max_len is left alone: a stale upper bound only widens the search
+    fn remove(&mut self, start: u32, stop: u32) -> bool {
+        let first = lower_bound(&self.ivs, start);
+        match self.ivs[first..]
+            .iter()
+            .take_while(|iv| iv.start == start)
+            .position(|iv| iv.stop == stop)
+        {
+            Some(i) => {
+                self.ivs.remove(first + i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Index of the first interval with `start >= key`.
+fn lower_bound(ivs: &[Iv], key: u32) -> usize {
+    let mut low = 0;
+    let mut high = ivs.len();
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if ivs[mid].start < key {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
@@ -0,0 +1,132 @@
+use crate::Iv;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Write};
+
+/// Maps contig names to the dense ids stored on `Iv`, shared by every set
+/// loaded for one run so that ids agree between them.
+#[derive(Debug, Default)]
+pub struct Contigs {
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+}
+
+impl Contigs {
+    pub fn from_names(names: Vec<String>) -> Self {
+        let mut contigs = Contigs::default();
+        for name in names.iter() {
+            contigs.id(name);
+        }
+        contigs
+    }
+
+    /// The id for `name`, assigning the next free one if it is new.
+    pub fn id(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn into_names(self) -> Vec<String> {
+        self.names
+    }
+}
+
+/// Parse one BED line into an `Iv`. Header, track and comment lines, as well
+/// as blank lines, yield `None`.
+pub fn parse_line(line: &str, contigs: &mut Contigs) -> io::Result<Option<Iv>> {
+    if line.is_empty()
+        || line.starts_with('#')
+        || line.starts_with("track")
+        || line.starts_with("browser")
+    {
+        return Ok(None);
+    }
+    let bad_line = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Bad bed line: {}", line),
+        )
+    };
+    let mut fields = line.split('\t');
+    let chrom = fields.next().ok_or_else(bad_line)?;
+    let start = fields
+        .next()
+        .and_then(|s| s.parse::<u32>().ok())
+        .ok_or_else(bad_line)?;
+    let stop = fields
+        .next()
+        .and_then(|s| s.trim_end().parse::<u32>().ok())
+        .ok_or_else(bad_line)?;
+    Ok(Some(Iv {
+        contig: contigs.id(chrom),
+        start,
+        stop,
+    }))
+}
+
+/// Open `path` for reading, where `-` means stdin. The input is only ever
+/// read front to back, so named pipes and process substitutions work too.
+pub fn open(path: &str) -> io::Result<Box<dyn BufRead>> {
+    if path == "-" {
+        Ok(Box::new(BufReader::new(io::stdin())))
+    } else {
+        Ok(Box::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+/// Lazily parses intervals out of a BED stream, one line at a time.
+pub struct BedRecords<'a, R> {
+    lines: Lines<R>,
+    contigs: &'a mut Contigs,
+}
+
+impl<'a, R: BufRead> BedRecords<'a, R> {
+    pub fn new(reader: R, contigs: &'a mut Contigs) -> Self {
+        BedRecords {
+            lines: reader.lines(),
+            contigs,
+        }
+    }
+}
+
+impl<'a, R: BufRead> Iterator for BedRecords<'a, R> {
+    type Item = io::Result<Iv>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let contigs = &mut *self.contigs;
+        for line in &mut self.lines {
+            match line.and_then(|line| parse_line(&line, contigs)) {
+                Ok(None) => continue,
+                Ok(Some(iv)) => return Some(Ok(iv)),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+/// Read every interval in a BED file, stdin or pipe.
+pub fn read_bed(path: &str, contigs: &mut Contigs) -> io::Result<Vec<Iv>> {
+    BedRecords::new(open(path)?, contigs).collect()
+}
+
+pub fn write_bed(path: &str, ivs: &[Iv], contigs: &[String]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for iv in ivs {
+        writeln!(
+            writer,
+            "{}\t{}\t{}",
+            contigs[iv.contig as usize], iv.start, iv.stop
+        )?;
+    }
+    writer.flush()
+}
@@ -0,0 +1,227 @@
+use crate::adapters::{Adapter, Config};
+use crate::Iv;
+
+/// Shape of the bins: the finest are `2^min_shift` wide, and there are
+/// `depth` levels below the root. The default is the CSI scheme, which
+/// covers the whole u32 coordinate space; tabix/BAI are min_shift 14,
+/// depth 5.
+#[derive(Clone, Copy, Debug)]
+pub struct Scheme {
+    pub min_shift: u32,
+    pub depth: u32,
+    /// Whether to keep a linear index alongside the bins.
+    pub linear: bool,
+}
+
+impl Default for Scheme {
+    fn default() -> Self {
+        Scheme {
+            min_shift: 14,
+            depth: 6,
+            linear: true,
+        }
+    }
+}
+
+impl Scheme {
+    pub fn new(min_shift: u32, depth: u32, linear: bool) -> Scheme {
+        // Bin ids are u32, and 10 levels below the root is the most whose
+        // ids all fit
+        assert!(depth <= 10, "bin_depth must be at most 10");
+        assert!(
+            min_shift + 3 * depth <= 40,
+            "bin_min_shift + 3 * bin_depth must be at most 40"
+        );
+        Scheme {
+            min_shift,
+            depth,
+            linear,
+        }
+    }
+}
+
+/// The UCSC hierarchical binning index as used by tabix, BAI and CSI. Level
+/// `l` of `depth` splits the coordinate space into `8^l` bins, the finest
+/// being `2^min_shift` wide, and each interval goes in the smallest bin that
+/// holds it whole. A query visits every bin at every level that overlaps it.
+///
+/// Intervals are stored sorted by start, and each bin lists the positions of
+/// its members in that order, just as a tabix bin lists file offsets. The
+/// optional linear index records, for each `2^min_shift` window, the first
+/// position of any interval reaching that window, so members before it can
+/// be skipped without being looked at.
+pub struct Binning {
+    starts: Vec<u32>,
+    stops: Vec<u32>,
+    bin_ids: Vec<u32>,
+    bin_offsets: Vec<u32>,
+    members: Vec<u32>,
+    linear: Option<Vec<u32>>,
+    min_shift: u32,
+    depth: u32,
+}
+
+impl Binning {
+    pub fn new(mut ivs: Vec<(u32, u32)>, scheme: Scheme) -> Self {
+        let Scheme {
+            min_shift,
+            depth,
+            linear,
+        } = scheme;
+        ivs.sort_unstable();
+        let mut binned: Vec<(u32, u32)> = ivs
+            .iter()
+            .enumerate()
+            .map(|(i, &(start, stop))| (reg2bin(start, stop, min_shift, depth), i as u32))
+            .collect();
+        binned.sort_unstable();
+
+        let mut bin_ids = vec![];
+        let mut bin_offsets = vec![];
+        for (i, &(bin, _)) in binned.iter().enumerate() {
+            if bin_ids.last() != Some(&bin) {
+                bin_ids.push(bin);
+                bin_offsets.push(i as u32);
+            }
+        }
+        bin_offsets.push(binned.len() as u32);
+
+        let linear = if linear {
+            Some(linear_index(&ivs, min_shift, depth))
+        } else {
+            None
+        };
+
+        Binning {
+            starts: ivs.iter().map(|iv| iv.0).collect(),
+            stops: ivs.iter().map(|iv| iv.1).collect(),
+            bin_ids,
+            bin_offsets,
+            members: binned.into_iter().map(|(_, i)| i).collect(),
+            linear,
+            min_shift,
+            depth,
+        }
+    }
+
+    pub fn count(&self, start: u32, stop: u32) -> usize {
+        let (beg, end) = clamp(start, stop, self.min_shift, self.depth);
+        let first = match self.linear {
+            Some(ref linear) => match linear.get((beg >> self.min_shift) as usize) {
+                Some(&first) => first,
+                None => return 0,
+            },
+            None => 0,
+        };
+        let mut count = 0;
+        for level in 0..=self.depth {
+            let shift = self.min_shift + 3 * (self.depth - level);
+            let offset = level_offset(level);
+            let lo = offset + (beg >> shift) as u32;
+            let hi = offset + (end >> shift) as u32;
+            let mut b = lower_bound(&self.bin_ids, lo);
+            while b < self.bin_ids.len() && self.bin_ids[b] <= hi {
+                let bin =
+                    &self.members[self.bin_offsets[b] as usize..self.bin_offsets[b + 1] as usize];
+                for &i in &bin[lower_bound(bin, first)..] {
+                    let i = i as usize;
+                    if self.starts[i] >= stop {
+                        break;
+                    }
+                    if self.stops[i] > start {
+                        count += 1;
+                    }
+                }
+                b += 1;
+            }
+        }
+        count
+    }
+}
+
+/// Number of bins in all levels above `level`.
+fn level_offset(level: u32) -> u32 {
+    (((1u64 << (3 * level)) - 1) / 7) as u32
+}
+
+/// The inclusive range of positions `start..stop` touches, clamped to the
+/// space the scheme covers. Anything past the end lands in the last bins,
+/// which costs speed but not correctness.
+fn clamp(start: u32, stop: u32, min_shift: u32, depth: u32) -> (u64, u64) {
+    let max_pos = (1u64 << (min_shift + 3 * depth)) - 1;
+    let beg = (start as u64).min(max_pos);
+    let end = (stop as u64).max(start as u64 + 1) - 1;
+    let end = end.min(max_pos);
+    (beg, end)
+}
+
+/// `hts_reg2bin`: the smallest bin holding all of `start..stop`.
+fn reg2bin(start: u32, stop: u32, min_shift: u32, depth: u32) -> u32 {
+    let (beg, end) = clamp(start, stop, min_shift, depth);
+    let mut shift = min_shift;
+    for level in (1..=depth).rev() {
+        if beg >> shift == end >> shift {
+            return level_offset(level) + (beg >> shift) as u32;
+        }
+        shift += 3;
+    }
+    0
+}
+
+/// First position, in start order, of an interval reaching each window.
+/// Windows nothing reaches take the value of the window before them, as
+/// tabix does, which is always safe since earlier positions start earlier.
+fn linear_index(ivs: &[(u32, u32)], min_shift: u32, depth: u32) -> Vec<u32> {
+    let mut linear: Vec<Option<u32>> = vec![];
+    for (i, &(start, stop)) in ivs.iter().enumerate() {
+        let (beg, end) = clamp(start, stop, min_shift, depth);
+        let (first, last) = ((beg >> min_shift) as usize, (end >> min_shift) as usize);
+        if linear.len() <= last {
+            linear.resize(last + 1, None);
+        }
+        for window in linear[first..=last].iter_mut() {
+            if window.is_none() {
+                *window = Some(i as u32);
+            }
+        }
+    }
+    let mut previous = 0;
+    linear
+        .into_iter()
+        .map(|window| {
+            previous = window.unwrap_or(previous);
+            previous
+        })
+        .collect()
+}
+
+fn lower_bound(values: &[u32], key: u32) -> usize {
+    let mut low = 0;
+    let mut high = values.len();
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if values[mid] < key {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+impl Adapter for Binning {
+    const NAME: &'static str = "binning";
+    type Item = (u32, u32);
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item> {
+        ivs.iter().map(|iv| (iv.start, iv.stop)).collect()
+    }
+
+    fn build(items: Vec<Self::Item>, config: &Config) -> Self {
+        Binning::new(items, config.binning)
+    }
+
+    fn find(&self, start: u32, stop: u32) -> usize {
+        self.count(start, stop)
+    }
+}
@@ -0,0 +1,211 @@
+use crate::Iv;
+use memmap::Mmap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+
+const MAGIC: &[u8; 8] = b"IVCACHE3";
+
+/// A memory mapped interval set in the binary cache format. All integers
+/// are little endian:
+///
+/// ```text
+/// magic          8 bytes, "IVCACHE3"
+/// num_contigs    u32
+/// contigs        num_contigs x (u32 name length, utf8 name)
+/// num_intervals  u64
+/// contig ids     num_intervals x u32
+/// starts         num_intervals x u32
+/// stops          num_intervals x u32
+/// payload index  num_intervals x u32
+/// checksum       u64, FNV-1a over every preceding byte
+/// ```
+///
+/// The payload index is the interval's position in the set it was written
+/// from, so data kept alongside the original (BED name columns etc.) can be
+/// joined back after a load.
+pub struct IvCache {
+    mmap: Mmap,
+    pub contigs: Vec<String>,
+    len: usize,
+    columns: usize,
+}
+
+impl IvCache {
+    /// Map `path` and validate its magic and checksum.
+    pub fn open(path: &str) -> io::Result<IvCache> {
+        let file = File::open(path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        let invalid =
+            |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, msg));
+        if mmap.len() < MAGIC.len() + 4 + 8 + 8 || &mmap[..MAGIC.len()] != MAGIC {
+            return Err(invalid("not an interval cache"));
+        }
+        let body = mmap.len() - 8;
+        if fnv1a(FNV_OFFSET, &mmap[..body]) != read_u64(&mmap, body) {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        // Every length below comes from the file, so check each one fits
+        // in the body before slicing by it
+        let mut offset = MAGIC.len();
+        let mut take = |n: usize| match offset.checked_add(n) {
+            Some(end) if end <= body => {
+                let start = offset;
+                offset = end;
+                Ok(&mmap[start..end])
+            }
+            _ => Err(invalid("truncated contig names")),
+        };
+        let num_contigs = read_u32(take(4)?, 0) as usize;
+        let mut contigs = vec![];
+        for _ in 0..num_contigs {
+            let len = read_u32(take(4)?, 0) as usize;
+            let name =
+                std::str::from_utf8(take(len)?).map_err(|_| invalid("contig name is not utf8"))?;
+            contigs.push(name.to_string());
+        }
+        let len = read_u64(take(8)?, 0) as usize;
+        if len.checked_mul(16) != Some(body - offset) {
+            return Err(invalid("truncated interval columns"));
+        }
+        Ok(IvCache {
+            mmap,
+            contigs,
+            len,
+            columns: offset,
+        })
+    }
+
+    /// The `i`th interval. Its contig id indexes `self.contigs`.
+    pub fn get(&self, i: usize) -> Iv {
+        Iv {
+            contig: self.column(0, i),
+            start: self.column(1, i),
+            stop: self.column(2, i),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Iv> + '_ {
+        (0..self.len).map(move |i| self.get(i))
+    }
+
+    /// Every interval with its payload index.
+    pub fn indexed(&self) -> impl Iterator<Item = (u32, Iv)> + '_ {
+        (0..self.len).map(move |i| (self.column(3, i), self.get(i)))
+    }
+
+    fn column(&self, column: usize, i: usize) -> u32 {
+        read_u32(&self.mmap, self.columns + (column * self.len + i) * 4)
+    }
+}
+
+/// True if `path` starts with the cache magic, so callers can accept either
+/// a BED file or a cache for the same argument.
+pub fn is_cache(path: &str) -> io::Result<bool> {
+    let mut magic = [0u8; 8];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == MAGIC),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn write_cache(path: &str, ivs: &[Iv], contigs: &[String]) -> io::Result<()> {
+    let mut writer = ChecksumWriter {
+        inner: BufWriter::new(File::create(path)?),
+        hash: FNV_OFFSET,
+    };
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(contigs.len() as u32).to_le_bytes())?;
+    for name in contigs {
+        writer.write_all(&(name.len() as u32).to_le_bytes())?;
+        writer.write_all(name.as_bytes())?;
+    }
+    writer.write_all(&(ivs.len() as u64).to_le_bytes())?;
+    for iv in ivs {
+        writer.write_all(&iv.contig.to_le_bytes())?;
+    }
+    for iv in ivs {
+        writer.write_all(&iv.start.to_le_bytes())?;
+    }
+    for iv in ivs {
+        writer.write_all(&iv.stop.to_le_bytes())?;
+    }
+    for i in 0..ivs.len() {
+        writer.write_all(&(i as u32).to_le_bytes())?;
+    }
+    let hash = writer.hash;
+    writer.inner.write_all(&hash.to_le_bytes())?;
+    writer.inner.flush()
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Hashes everything written through it so the checksum never needs a
+/// second pass over the file.
+struct ChecksumWriter<W> {
+    inner: W,
+    hash: u64,
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hash = fnv1a(self.hash, &buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn payload_indices_round_trip() {
+        let ivs: Vec<Iv> = (0..5)
+            .map(|i| Iv {
+                contig: i % 2,
+                start: i * 10,
+                stop: i * 10 + 3,
+            })
+            .collect();
+        let contigs = vec![String::from("chr1"), String::from("chr2")];
+        let path = env::temp_dir().join(format!("interval_bakeoff.{}.ivc", process::id()));
+        let path = path.display().to_string();
+        write_cache(&path, &ivs, &contigs).unwrap();
+        let cache = IvCache::open(&path).unwrap();
+        let read: Vec<(u32, Iv)> = cache.indexed().collect();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(cache.contigs, contigs);
+        assert_eq!(
+            read,
+            ivs.into_iter()
+                .enumerate()
+                .map(|(i, iv)| (i as u32, iv))
+                .collect::<Vec<_>>()
+        );
+    }
+}
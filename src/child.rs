@@ -0,0 +1,213 @@
+//! Running one lib, or one part of a lib's run, in a child process. A lib
+//! that runs too long or grows too big can then be stopped without stopping
+//! the bakeoff, and no lib runs on a heap or allocator another lib left
+//! behind. The child is this binary again with the same arguments plus
+//! `--child`, which names the lib, the dataset and repeat to run it on, the
+//! seed the data was made from, the file to stream its records to, and the
+//! part to run. Everything else the child works out from the arguments just
+//! as the parent did.
+
+use crate::{mem, output, results, Lib};
+use clap::ArgMatches;
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::process::{Command, ExitStatus};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use std::{env, fs, process, thread};
+
+/// Limits on each lib's run over a dataset.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Budget {
+    /// Longest any one build or workload may take, by wall clock.
+    pub secs: Option<f64>,
+    /// Most the process may grow by once the dataset is loaded.
+    pub bytes: Option<usize>,
+}
+
+/// How finely `--isolate` splits a run into child processes.
+#[derive(Clone, Copy, Debug)]
+pub enum Isolate {
+    /// A child per lib.
+    Lib,
+    /// A child per lib for its builds, then another for each workload and
+    /// one for the dynamic workload.
+    Workload,
+}
+
+impl Isolate {
+    pub fn parse(value: &str) -> Isolate {
+        match value {
+            "lib" => Isolate::Lib,
+            "workload" => Isolate::Workload,
+            _ => panic!("Unknown isolation: {}", value),
+        }
+    }
+}
+
+/// What the parent asked this process to run.
+pub struct Job {
+    pub lib: Lib,
+    pub dataset: String,
+    pub repeat: usize,
+    pub seed: u64,
+    results: String,
+    /// `build`, `dynamic` or a workload key, or everything if `None`.
+    part: Option<String>,
+}
+
+static JOB: OnceLock<Job> = OnceLock::new();
+// Stdout from before set up was silenced, since the parent printed all that
+static SAVED: Mutex<Option<output::Saved>> = Mutex::new(None);
+
+/// If `--child` was given, note the job and silence stdout until the lib
+/// starts.
+pub fn init(matches: &ArgMatches) {
+    let values: Vec<&str> = match matches.values_of("child") {
+        Some(values) => values.collect(),
+        None => return,
+    };
+    let job = Job {
+        lib: values[0].parse::<Lib>().expect("Unknown child lib"),
+        dataset: values[1].to_string(),
+        repeat: values[2].parse().expect("Bad child repeat"),
+        seed: values[3].parse().expect("Bad child seed"),
+        results: values[4].to_string(),
+        part: Some(values[5].to_string()).filter(|part| part != "all"),
+    };
+    *SAVED.lock().unwrap() = Some(output::silence().expect("Failed to silence stdout"));
+    let _ = JOB.set(job);
+}
+
+/// The job, if this process is a child.
+pub fn job() -> Option<&'static Job> {
+    JOB.get()
+}
+
+/// False if this is a child asked to run some part other than `part`, one
+/// of `build`, `dynamic` or a workload key. Every child builds its indices,
+/// but only the `build` part reports them.
+pub fn runs(part: &str) -> bool {
+    match job().and_then(|job| job.part.as_ref()) {
+        Some(only) => only == part,
+        None => true,
+    }
+}
+
+/// In a child, called just before its lib runs: bring stdout back, start
+/// streaming records, and start the memory budget from the memory in use
+/// now.
+pub fn start(budget: Option<Budget>) {
+    let job = match job() {
+        Some(job) => job,
+        None => return,
+    };
+    if let Some(saved) = SAVED.lock().unwrap().take() {
+        output::restore(saved).expect("Failed to restore stdout");
+    }
+    results::stream_to(&job.results).expect("Failed to open child results");
+    if let Some(bytes) = budget.and_then(|b| b.bytes) {
+        mem::limit_growth(bytes);
+    }
+}
+
+/// Run `part` of `lib`, whose adapter is called `name`, or all of it, on
+/// the current dataset and repeat in a child, within `budget`, and keep
+/// what it recorded. If the child goes over budget or fails, say so and
+/// record it against the step it was on. The time budget only counts once
+/// the lib has started, and starts over with every record, so it bounds
+/// each step rather than the whole run.
+pub fn run(lib: Lib, name: &str, seed: u64, budget: Budget, part: Option<&str>) {
+    let (dataset, repeat) = results::context();
+    let part = part.unwrap_or("all");
+    let path = env::temp_dir().join(format!(
+        "interval_bakeoff.{}.{:?}.{}.tsv",
+        process::id(),
+        lib,
+        part
+    ));
+    let mut args: Vec<OsString> = env::args_os().skip(1).collect();
+    args.push(OsString::from("--child"));
+    for value in [
+        format!("{:?}", lib),
+        dataset,
+        repeat.to_string(),
+        seed.to_string(),
+        path.display().to_string(),
+        part.to_string(),
+    ] {
+        args.push(OsString::from(value));
+    }
+    io::stdout().flush().expect("Failed to flush stdout");
+    let mut child = Command::new(env::current_exe().expect("Failed to find this binary"))
+        .args(&args)
+        .spawn()
+        .expect("Failed to start child");
+
+    let limit = budget.secs.map(Duration::from_secs_f64);
+    let mut progress: Option<(u64, Instant)> = None;
+    let status = loop {
+        if let Some(status) = child.try_wait().expect("Failed to wait for child") {
+            break Some(status);
+        }
+        if let Ok(metadata) = fs::metadata(&path) {
+            match progress {
+                Some((len, _)) if len == metadata.len() => (),
+                _ => progress = Some((metadata.len(), Instant::now())),
+            }
+        }
+        if let (Some(limit), Some((_, since))) = (limit, progress) {
+            if since.elapsed() > limit {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let table = results::Table::read(&path.display().to_string()).ok();
+    let _ = fs::remove_file(&path);
+    let step = table
+        .as_ref()
+        .and_then(|t| t.env.iter().rev().find(|(key, _)| key == "step"))
+        .map_or("set up", |(_, label)| label.as_str())
+        .to_string();
+    if let Some(records) = table.as_ref().and_then(|t| t.records()) {
+        results::extend(records);
+    }
+    match status {
+        None => {
+            let secs = budget.secs.unwrap();
+            println!("{}: timed out after {}s in {}", name, secs, step);
+            results::record(name, &step, "timed_out", secs);
+        }
+        Some(status) if status.success() => (),
+        Some(status) if budget.bytes.is_some() && aborted(status) => {
+            let bytes = budget.bytes.unwrap();
+            println!(
+                "{}: went over the memory budget of {} in {}",
+                name,
+                mem::human_bytes(bytes as u64),
+                step
+            );
+            results::record(name, &step, "out_of_memory", bytes as f64);
+        }
+        Some(status) => {
+            println!("{}: failed in {}: {}", name, step, status);
+            results::record(name, &step, "failed", 1.0);
+        }
+    }
+}
+
+/// True if the child aborted, which is how a failed allocation ends it.
+#[cfg(unix)]
+fn aborted(status: ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal() == Some(libc::SIGABRT)
+}
+
+#[cfg(not(unix))]
+fn aborted(_status: ExitStatus) -> bool {
+    false
+}
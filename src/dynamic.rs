@@ -0,0 +1,210 @@
+use crate::adapters::{split_by_contig, Adapter, ContigIndex};
+use crate::baselines::overlaps;
+use crate::{check_count, results, with_rng, Iv};
+use cpu_time::ProcessTime;
+use rand::Rng;
+use std::time::Duration;
+
+/// Shape of the mixed update and query workload.
+#[derive(Clone, Copy, Debug)]
+pub struct Mix {
+    pub ops: usize,
+    pub inserts: u32,
+    pub deletes: u32,
+    pub queries: u32,
+    /// Updates a library without in place mutation batches up before
+    /// rebuilding. 1 rebuilds on every update. Without it such libraries
+    /// skip the workload.
+    pub rebuild_every: Option<usize>,
+}
+
+impl Mix {
+    /// `ratios` are relative weights written `inserts:deletes:queries`.
+    pub fn new(ops: usize, ratios: &str, rebuild_every: Option<usize>) -> Mix {
+        let weights: Vec<u32> = ratios
+            .split(':')
+            .map(|w| w.parse::<u32>().expect("Bad dynamic_mix weight"))
+            .collect();
+        assert!(
+            weights.len() == 3 && weights.iter().sum::<u32>() > 0,
+            "dynamic_mix must be three weights, inserts:deletes:queries"
+        );
+        assert!(
+            rebuild_every != Some(0),
+            "rebuild_every must be greater than 0"
+        );
+        Mix {
+            ops,
+            inserts: weights[0],
+            deletes: weights[1],
+            queries: weights[2],
+            rebuild_every,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Op {
+    Insert(Iv),
+    Delete(Iv),
+    Query(Iv),
+}
+
+/// Draw `mix.ops` operations against the live set `initial`. New intervals
+/// and queries are picked from `pool`; deletes pick whatever is live at that
+/// point, so every lib is asked to delete the same, existing intervals. A
+/// delete drawn while nothing is live becomes an insert.
+pub fn make_ops(initial: &[Iv], pool: &[Iv], mix: &Mix) -> Vec<Op> {
+    assert!(
+        !pool.is_empty(),
+        "The dynamic workload needs a non-empty set a"
+    );
+    let mut live = initial.to_vec();
+    let total = mix.inserts + mix.deletes + mix.queries;
+    let mut ops = Vec::with_capacity(mix.ops);
+    with_rng(|rng| {
+        for _ in 0..mix.ops {
+            let roll = rng.gen_range(0, total);
+            let pick = pool[rng.gen_range(0, pool.len())];
+            if roll < mix.inserts || (roll < mix.inserts + mix.deletes && live.is_empty()) {
+                live.push(pick);
+                ops.push(Op::Insert(pick));
+            } else if roll < mix.inserts + mix.deletes {
+                let victim = live.swap_remove(rng.gen_range(0, live.len()));
+                ops.push(Op::Delete(victim));
+            } else {
+                ops.push(Op::Query(pick));
+            }
+        }
+    });
+    ops
+}
+
+/// Total overlaps of every query in `ops`, each against the live set as it
+/// stands right then.
+pub fn expected_hits(initial: &[Iv], ops: &[Op]) -> usize {
+    let mut live = initial.to_vec();
+    let mut hits = 0;
+    for op in ops {
+        match op {
+            Op::Insert(iv) => live.push(*iv),
+            Op::Delete(iv) => {
+                let i = live.iter().position(|x| x == iv).unwrap();
+                live.swap_remove(i);
+            }
+            Op::Query(q) => {
+                hits += live
+                    .iter()
+                    .filter(|iv| iv.contig == q.contig && overlaps(iv, q.start, q.stop))
+                    .count()
+            }
+        }
+    }
+    hits
+}
+
+/// Apply `ops` to `index`, which must hold exactly `initial`. Updates the
+/// library can make in place are timed one by one. Any other update only
+/// marks its contig dirty, and once `rebuild_every` of them are pending the
+/// dirty contigs are rebuilt from their live sets, which is what those
+/// updates cost. Queries in between see the index as of the last rebuild,
+/// so their counts are only checked when nothing is ever stale.
+pub fn run<A: Adapter>(
+    index: &mut ContigIndex<A>,
+    initial: &[Iv],
+    num_contigs: usize,
+    ops: &[Op],
+    rebuild_every: usize,
+    expected: Option<usize>,
+) {
+    let mut live = split_by_contig(initial, num_contigs);
+    let mut dirty = vec![false; num_contigs];
+    let mut pending = 0;
+    let mut times = [Duration::default(); 4];
+    let (insert, delete, rebuild, query) = (0, 1, 2, 3);
+    let mut counts = [0; 4];
+    let mut hits = 0;
+    for op in ops {
+        match op {
+            Op::Insert(iv) => {
+                live[iv.contig as usize].push(*iv);
+                counts[insert] += 1;
+                if A::HAS_INSERT {
+                    let start = ProcessTime::now();
+                    index.insert(iv);
+                    times[insert] += start.elapsed();
+                } else {
+                    dirty[iv.contig as usize] = true;
+                    pending += 1;
+                }
+            }
+            Op::Delete(iv) => {
+                let contig = &mut live[iv.contig as usize];
+                let i = contig.iter().position(|x| x == iv).unwrap();
+                contig.swap_remove(i);
+                counts[delete] += 1;
+                if A::HAS_REMOVE {
+                    let start = ProcessTime::now();
+                    let removed = index.remove(iv);
+                    times[delete] += start.elapsed();
+                    assert!(removed, "{} lost {:?}", A::NAME, iv);
+                } else {
+                    dirty[iv.contig as usize] = true;
+                    pending += 1;
+                }
+            }
+            Op::Query(iv) => {
+                counts[query] += 1;
+                let start = ProcessTime::now();
+                hits += index.find(iv);
+                times[query] += start.elapsed();
+            }
+        }
+        if pending >= rebuild_every {
+            let start = ProcessTime::now();
+            for (contig, dirty) in dirty.iter_mut().enumerate().filter(|(_, d)| **d) {
+                index.rebuild(contig, &live[contig]);
+                *dirty = false;
+            }
+            times[rebuild] += start.elapsed();
+            counts[rebuild] += 1;
+            pending = 0;
+        }
+    }
+
+    let how = |native| if native { "in place" } else { "via rebuild" };
+    println!(
+        "{}: dynamic inserts ({}) time/count: {:#?}/{}",
+        A::NAME,
+        how(A::HAS_INSERT),
+        times[insert],
+        counts[insert]
+    );
+    println!(
+        "{}: dynamic deletes ({}) time/count: {:#?}/{}",
+        A::NAME,
+        how(A::HAS_REMOVE),
+        times[delete],
+        counts[delete]
+    );
+    println!(
+        "{}: dynamic rebuilds every {} updates time/count: {:#?}/{}",
+        A::NAME,
+        rebuild_every,
+        times[rebuild],
+        counts[rebuild]
+    );
+    println!(
+        "{}: dynamic queries time/count: {:#?}/{}",
+        A::NAME,
+        times[query],
+        hits
+    );
+    let total = times.iter().sum::<Duration>();
+    println!("{}: dynamic total time: {:#?}", A::NAME, total);
+    results::record(A::NAME, "dynamic", "query_secs", total.as_secs_f64());
+    let exact = rebuild_every == 1 || (A::HAS_INSERT && A::HAS_REMOVE);
+    if exact {
+        check_count(A::NAME, "dynamic queries", hits, expected);
+    }
+}
@@ -0,0 +1,63 @@
+use std::fs;
+use std::thread;
+
+mod build {
+    include!(concat!(env!("OUT_DIR"), "/build_info.rs"));
+}
+
+/// What a result depends on besides the code: how the binary was built and
+/// the machine it ran on, as `(key, value)` pairs.
+pub fn capture() -> Vec<(String, String)> {
+    let mut result = vec![
+        (String::from("git_rev"), build::GIT_REV.to_string()),
+        (String::from("rustc"), build::RUSTC.to_string()),
+        (String::from("profile"), build::PROFILE.to_string()),
+        (String::from("target"), build::TARGET.to_string()),
+        (
+            String::from("target_features"),
+            build::TARGET_FEATURES.to_string(),
+        ),
+        (String::from("cpu"), cpu_model()),
+        (
+            String::from("cores"),
+            thread::available_parallelism()
+                .map(|n| n.to_string())
+                .unwrap_or_else(|_| String::from("unknown")),
+        ),
+        (String::from("kernel"), kernel()),
+    ];
+    for (name, version, path, rev) in build::PATH_DEPS.iter() {
+        result.push((
+            format!("dep {}", name),
+            format!("{} at {} ({})", version, path, rev),
+        ));
+    }
+    result
+}
+
+/// Print the environment in the same layout as the run's config.
+pub fn print() {
+    println!("Environment:");
+    for (key, value) in capture() {
+        println!("{}\t{}", key, value);
+    }
+}
+
+fn cpu_model() -> String {
+    fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .and_then(|info| {
+            info.lines()
+                .find(|line| line.starts_with("model name"))
+                .and_then(|line| line.split_once(':'))
+                .map(|(_, model)| model.trim().to_string())
+        })
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+fn kernel() -> String {
+    match fs::read_to_string("/proc/sys/kernel/osrelease") {
+        Ok(release) => format!("{} {}", std::env::consts::OS, release.trim()),
+        Err(_) => std::env::consts::OS.to_string(),
+    }
+}
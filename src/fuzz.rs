@@ -0,0 +1,385 @@
+//! Differential fuzzing. Small random and edge case sets are indexed by a
+//! lib and by the naive oracle, every query is answered by both, and a case
+//! they disagree on is shrunk to the fewest intervals and queries that
+//! still show it.
+
+use crate::adapters::{Adapter, Config, ContigIndex};
+use crate::baselines::Naive;
+use crate::Iv;
+use rand::Rng;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Contigs every case spreads over, so routing between them gets checked.
+pub const NUM_CONTIGS: usize = 2;
+
+/// Intervals to index and the queries to run against them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Case {
+    pub ivs: Vec<Iv>,
+    pub queries: Vec<Iv>,
+}
+
+/// The name contig `id` is written under in reproductions.
+pub fn contig_name(id: u32) -> String {
+    format!("chr{}", id + 1)
+}
+
+fn iv(contig: u32, start: u32, stop: u32) -> Iv {
+    Iv {
+        contig,
+        start,
+        stop,
+    }
+}
+
+/// Cases with a name each, for the inputs libraries most often get wrong.
+pub fn edge_cases() -> Vec<(&'static str, Case)> {
+    let max = u32::MAX;
+    vec![
+        (
+            "empty set",
+            Case {
+                ivs: vec![],
+                queries: vec![iv(0, 0, 10), iv(0, 5, 5), iv(1, 0, max)],
+            },
+        ),
+        (
+            "zero length intervals",
+            Case {
+                ivs: vec![iv(0, 5, 5), iv(0, 0, 0), iv(0, 10, 10), iv(0, 0, 20)],
+                queries: vec![
+                    iv(0, 5, 5),
+                    iv(0, 4, 6),
+                    iv(0, 0, 1),
+                    iv(0, 9, 10),
+                    iv(0, 10, 11),
+                ],
+            },
+        ),
+        (
+            "duplicates",
+            Case {
+                ivs: vec![
+                    iv(0, 10, 20),
+                    iv(0, 10, 20),
+                    iv(0, 10, 20),
+                    iv(0, 15, 25),
+                    iv(0, 15, 25),
+                ],
+                queries: vec![
+                    iv(0, 0, 10),
+                    iv(0, 0, 11),
+                    iv(0, 19, 20),
+                    iv(0, 20, 21),
+                    iv(0, 24, 30),
+                ],
+            },
+        ),
+        (
+            "start == stop queries",
+            Case {
+                ivs: vec![iv(0, 0, 10), iv(0, 10, 20), iv(0, 5, 15)],
+                queries: vec![
+                    iv(0, 0, 0),
+                    iv(0, 5, 5),
+                    iv(0, 10, 10),
+                    iv(0, 15, 15),
+                    iv(0, 20, 20),
+                ],
+            },
+        ),
+        (
+            "near u32::MAX",
+            Case {
+                ivs: vec![
+                    iv(0, max - 10, max),
+                    iv(0, max - 1, max),
+                    iv(0, 0, max),
+                    iv(0, max - 100, max - 50),
+                    iv(0, max, max),
+                ],
+                queries: vec![
+                    iv(0, max - 5, max),
+                    iv(0, max - 1, max),
+                    iv(0, max, max),
+                    iv(0, 0, 1),
+                    iv(0, max - 60, max - 55),
+                    iv(0, 0, max),
+                ],
+            },
+        ),
+        (
+            "nested",
+            Case {
+                ivs: (0..8).map(|i| iv(0, i * 10, 200 - i * 10)).collect(),
+                queries: vec![iv(0, 0, 5), iv(0, 75, 125), iv(0, 99, 100), iv(0, 195, 300)],
+            },
+        ),
+        (
+            "other contig",
+            Case {
+                ivs: vec![iv(1, 0, 100), iv(1, 50, 60)],
+                queries: vec![iv(0, 0, 100), iv(1, 55, 56), iv(1, 100, 200)],
+            },
+        ),
+    ]
+}
+
+/// A small random case. Coordinates sit near 0, near `u32::MAX`, or
+/// anywhere, lengths include zero, and some intervals and queries repeat.
+pub fn random_case<R: Rng>(rng: &mut R) -> Case {
+    let base = match rng.gen_range(0, 3) {
+        0 => 0,
+        1 => u32::MAX - 2_000,
+        _ => rng.gen_range(0, u32::MAX - 2_000),
+    };
+    let max_len = [1, 10, 200, 2_000][rng.gen_range(0, 4)];
+    let random_iv = |rng: &mut R| {
+        let start = base + rng.gen_range(0, 2_000);
+        let len = if rng.gen_bool(0.1) {
+            0
+        } else {
+            rng.gen_range(0, max_len)
+        };
+        iv(
+            rng.gen_range(0, NUM_CONTIGS as u32),
+            start,
+            start.saturating_add(len),
+        )
+    };
+    let make = |rng: &mut R, n: usize| {
+        let mut ivs: Vec<Iv> = Vec::with_capacity(n);
+        for _ in 0..n {
+            let next = match ivs.len() {
+                len if len > 0 && rng.gen_bool(0.1) => ivs[rng.gen_range(0, len)],
+                _ => random_iv(rng),
+            };
+            ivs.push(next);
+        }
+        ivs
+    };
+    let num_ivs = rng.gen_range(0, 40);
+    let num_queries = rng.gen_range(1, 20);
+    Case {
+        ivs: make(rng, num_ivs),
+        queries: make(rng, num_queries),
+    }
+}
+
+/// Run `case` through `A`, built with `config`, and the oracle, returning
+/// the first disagreement, or the panic message if `A` panicked. Counting
+/// and seeking are checked too where the lib has them.
+pub fn check<A: Adapter>(case: &Case, config: &Config) -> Result<(), String> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let oracle = ContigIndex::<Naive>::build(
+            ContigIndex::<Naive>::convert(&case.ivs, NUM_CONTIGS),
+            config,
+        );
+        let index =
+            ContigIndex::<A>::build(ContigIndex::<A>::convert(&case.ivs, NUM_CONTIGS), config);
+        let mismatch = |pass: &str, q: &Iv, expected: usize, got: usize| {
+            format!(
+                "{} {}:{}-{}: expected {} got {}",
+                pass,
+                contig_name(q.contig),
+                q.start,
+                q.stop,
+                expected,
+                got
+            )
+        };
+        for q in case.queries.iter() {
+            let expected = oracle.find(q);
+            let got = index.find(q);
+            if got != expected {
+                return Err(mismatch("find", q, expected, got));
+            }
+            if A::HAS_COUNT {
+                let got = index.count(q);
+                if got != expected {
+                    return Err(mismatch("count", q, expected, got));
+                }
+            }
+        }
+        if A::HAS_SEEK {
+            let mut sorted = case.queries.clone();
+            sorted.sort_by_key(|q| (q.contig, q.start));
+            let mut cursor = 0;
+            for (i, q) in sorted.iter().enumerate() {
+                if i > 0 && sorted[i - 1].contig != q.contig {
+                    cursor = 0;
+                }
+                let expected = oracle.find(q);
+                let got = index.seek(q, &mut cursor);
+                if got != expected {
+                    return Err(mismatch("seek", q, expected, got));
+                }
+            }
+        }
+        Ok(())
+    }));
+    match result {
+        Ok(result) => result,
+        Err(payload) => Err(match payload.downcast_ref::<&str>() {
+            Some(msg) => format!("panicked: {}", msg),
+            None => match payload.downcast_ref::<String>() {
+                Some(msg) => format!("panicked: {}", msg),
+                None => String::from("panicked"),
+            },
+        }),
+    }
+}
+
+/// Drop intervals and queries from `case` one at a time for as long as
+/// `fails` still holds, until none can go.
+pub fn shrink<F: Fn(&Case) -> bool>(mut case: Case, fails: F) -> Case {
+    loop {
+        let mut shrunk = false;
+        for i in (0..case.queries.len()).rev() {
+            let mut smaller = case.clone();
+            smaller.queries.remove(i);
+            if fails(&smaller) {
+                case = smaller;
+                shrunk = true;
+            }
+        }
+        for i in (0..case.ivs.len()).rev() {
+            let mut smaller = case.clone();
+            smaller.ivs.remove(i);
+            if fails(&smaller) {
+                case = smaller;
+                shrunk = true;
+            }
+        }
+        if !shrunk {
+            return case;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{adapters, baselines, binning, iitree, nclist};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn failures<A: Adapter>() -> Vec<String> {
+        let mut rng = StdRng::seed_from_u64(42);
+        let random = (0..300).map(|i| (format!("random case {}", i), random_case(&mut rng)));
+        edge_cases()
+            .into_iter()
+            .map(|(name, case)| (name.to_string(), case))
+            .chain(random)
+            .filter_map(|(name, case)| {
+                check::<A>(&case, &Config::default())
+                    .err()
+                    .map(|e| format!("{}: {}: {}", A::NAME, name, e))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn baselines_match_the_oracle() {
+        assert_eq!(failures::<baselines::Naive>(), Vec::<String>::new());
+        assert_eq!(failures::<baselines::BinarySearch>(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn nclist_matches_the_oracle() {
+        assert_eq!(failures::<nclist::NCList>(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn binning_matches_the_oracle() {
+        assert_eq!(failures::<binning::Binning>(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn iitree_matches_the_oracle() {
+        assert_eq!(failures::<iitree::IITree>(), Vec::<String>::new());
+    }
+
+    // Upstream libs may not agree with the oracle on every edge, which
+    // `fuzz` is there to show, so these only run when asked for with
+    // `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn upstream_libs_match_the_oracle() {
+        let mut all = vec![];
+        all.extend(failures::<adapters::RustLapper>());
+        all.extend(failures::<adapters::RustHopper>());
+        all.extend(failures::<adapters::RustBio>());
+        all.extend(failures::<adapters::IProxy>());
+        all.extend(failures::<adapters::NestedIntervals>());
+        all.extend(failures::<adapters::CoiTree>());
+        all.extend(failures::<adapters::AIList>());
+        assert_eq!(all, Vec::<String>::new());
+    }
+
+    #[test]
+    fn random_cases_are_well_formed() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..1000 {
+            let case = random_case(&mut rng);
+            for iv in case.ivs.iter().chain(case.queries.iter()) {
+                assert!(iv.start <= iv.stop);
+                assert!((iv.contig as usize) < NUM_CONTIGS);
+            }
+        }
+    }
+
+    #[test]
+    fn shrink_keeps_only_what_the_failure_needs() {
+        // Fails whenever some query hits two intervals
+        let fails = |case: &Case| {
+            let oracle = ContigIndex::<Naive>::build(
+                ContigIndex::<Naive>::convert(&case.ivs, NUM_CONTIGS),
+                &Config::default(),
+            );
+            case.queries.iter().any(|q| oracle.find(q) >= 2)
+        };
+        let case = Case {
+            ivs: vec![iv(0, 0, 10), iv(0, 5, 15), iv(0, 8, 9), iv(1, 0, 100)],
+            queries: vec![iv(0, 0, 1), iv(0, 6, 7), iv(1, 3, 4), iv(0, 8, 9)],
+        };
+        let shrunk = shrink(case, fails);
+        assert_eq!(shrunk.ivs.len(), 2);
+        assert_eq!(shrunk.queries.len(), 1);
+        assert!(fails(&shrunk));
+    }
+
+    #[test]
+    fn check_reports_a_wrong_count() {
+        // Misses intervals that end exactly one past the query start
+        struct OffByOne(Vec<Iv>);
+        impl Adapter for OffByOne {
+            const NAME: &'static str = "off-by-one";
+            type Item = Iv;
+            fn convert(ivs: &[Iv]) -> Vec<Iv> {
+                ivs.to_vec()
+            }
+            fn build(items: Vec<Iv>, _config: &Config) -> Self {
+                OffByOne(items)
+            }
+            fn find(&self, start: u32, stop: u32) -> usize {
+                self.0
+                    .iter()
+                    .filter(|iv| iv.start < stop && iv.stop > start + 1)
+                    .count()
+            }
+        }
+        let case = Case {
+            ivs: vec![iv(0, 0, 10)],
+            queries: vec![iv(0, 9, 20)],
+        };
+        assert_eq!(
+            check::<OffByOne>(&case, &Config::default()),
+            Err(String::from("find chr1:9-20: expected 1 got 0"))
+        );
+        assert_eq!(
+            check::<OffByOne>(&Case::default(), &Config::default()),
+            Ok(())
+        );
+    }
+}
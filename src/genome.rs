@@ -0,0 +1,142 @@
+use crate::{randomi, Iv};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+/// Contig names and lengths, as read from a `chrom.sizes` file.
+#[derive(Debug)]
+pub struct Genome {
+    pub contigs: Vec<(String, u32)>,
+}
+
+impl Genome {
+    /// Read a tab separated `name\tlength` file, ignoring blank lines,
+    /// `#` comment lines and any columns after the second.
+    pub fn from_file(path: &str) -> io::Result<Genome> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut contigs = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split('\t');
+            let name = fields.next().unwrap().to_string();
+            let length = fields
+                .next()
+                .and_then(|l| l.trim().parse::<u32>().ok())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Bad chrom.sizes line: {}", line),
+                    )
+                })?;
+            contigs.push((name, length));
+        }
+        let genome = Genome { contigs };
+        if genome.total_length() == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: no contigs with a length above 0", path),
+            ));
+        }
+        Ok(genome)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.contigs.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    pub fn total_length(&self) -> u64 {
+        self.contigs.iter().map(|(_, len)| *len as u64).sum()
+    }
+
+    /// Split `n` intervals across contigs in proportion to their length.
+    /// Rounding the cumulative share keeps the total exactly `n`.
+    pub fn allocate(&self, n: u32) -> Vec<u32> {
+        let total = self.total_length();
+        let mut result = Vec::with_capacity(self.contigs.len());
+        let mut cumulative = 0;
+        let mut assigned = 0;
+        for (_, len) in self.contigs.iter() {
+            cumulative += *len as u64;
+            let upto = ((n as u64 * cumulative + total / 2) / total) as u32;
+            result.push(upto - assigned);
+            assigned = upto;
+        }
+        result
+    }
+
+    /// Tile every contig with windows of `width`, the last one clipped at
+    /// the contig end. These are the queries of the genomecov workload.
+    pub fn windows(&self, width: u32) -> Vec<Iv> {
+        let mut result = vec![];
+        for (contig, (_, len)) in self.contigs.iter().enumerate() {
+            let mut start = 0;
+            while start < *len {
+                let stop = len.saturating_sub(start).min(width) + start;
+                result.push(Iv {
+                    contig: contig as u32,
+                    start,
+                    stop,
+                });
+                start = stop;
+            }
+        }
+        result
+    }
+
+    /// The regions of the genome not covered by any interval in `ivs`, as
+    /// `bedtools complement` would report them.
+    pub fn complement(&self, ivs: &[Iv]) -> Vec<Iv> {
+        let mut sorted: Vec<(u32, u32, u32)> =
+            ivs.iter().map(|i| (i.contig, i.start, i.stop)).collect();
+        sorted.sort_unstable();
+        let mut result = vec![];
+        let mut iter = sorted.into_iter().peekable();
+        for (contig, (_, len)) in self.contigs.iter().enumerate() {
+            let contig = contig as u32;
+            let mut covered = 0;
+            while let Some(&(c, start, stop)) = iter.peek() {
+                if c != contig {
+                    break;
+                }
+                if start > covered {
+                    result.push(Iv {
+                        contig,
+                        start: covered,
+                        stop: start.min(*len),
+                    });
+                }
+                covered = covered.max(stop);
+                iter.next();
+            }
+            if covered < *len {
+                result.push(Iv {
+                    contig,
+                    start: covered,
+                    stop: *len,
+                });
+            }
+        }
+        result.retain(|iv| iv.start < iv.stop);
+        result
+    }
+}
+
+/// Generate `n` intervals spread across the genome in proportion to contig
+/// length, clipping any interval that would run off the end of its contig.
+pub fn make_random_genome(genome: &Genome, n: u32, size_min: u32, size_max: u32) -> Vec<Iv> {
+    let mut result = Vec::with_capacity(n as usize);
+    for (contig, ((_, len), count)) in genome.contigs.iter().zip(genome.allocate(n)).enumerate() {
+        for _ in 0..count {
+            let s = randomi(0, *len);
+            let e = s.saturating_add(randomi(size_min, size_max)).min(*len);
+            result.push(Iv {
+                contig: contig as u32,
+                start: s,
+                stop: e,
+            });
+        }
+    }
+    result
+}
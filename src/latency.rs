@@ -0,0 +1,91 @@
+use crate::adapters::{Adapter, ContigIndex};
+use crate::Iv;
+use std::hint;
+use std::time::{Duration, Instant};
+
+/// Values below `2^SUB_BITS` get a bucket each, and every power of two
+/// above that is split into `2^(SUB_BITS - 1)` linear sub-buckets, so a
+/// bucket is never more than 1/64th wider than its lowest value: about two
+/// significant digits, as HDR histograms are usually configured.
+const SUB_BITS: u32 = 7;
+
+/// Log-linear histogram of nanosecond latencies, in the style of
+/// HdrHistogram.
+#[derive(Default)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    max: u64,
+}
+
+impl Histogram {
+    pub fn record(&mut self, nanos: u64) {
+        let i = bucket(nanos);
+        if self.counts.len() <= i {
+            self.counts.resize(i + 1, 0);
+        }
+        self.counts[i] += 1;
+        self.total += 1;
+        self.max = self.max.max(nanos);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max)
+    }
+
+    /// `(lowest value, count)` of every bucket anything landed in.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(i, &count)| (lowest(i), count))
+    }
+
+    /// Smallest bucket value at or below which a fraction `q` of recorded
+    /// latencies fall.
+    pub fn percentile(&self, q: f64) -> Duration {
+        let rank = ((q * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_nanos(lowest(i).min(self.max));
+            }
+        }
+        self.max()
+    }
+}
+
+fn bucket(nanos: u64) -> usize {
+    if nanos < 1 << SUB_BITS {
+        return nanos as usize;
+    }
+    let shift = 64 - nanos.leading_zeros() - SUB_BITS;
+    ((shift as usize) << (SUB_BITS - 1)) + (nanos >> shift) as usize
+}
+
+/// The lowest value that lands in bucket `i`.
+fn lowest(i: usize) -> u64 {
+    if i < 1 << SUB_BITS {
+        return i as u64;
+    }
+    let shift = (i >> (SUB_BITS - 1)) - 1;
+    ((i - (shift << (SUB_BITS - 1))) as u64) << shift
+}
+
+/// Time every `every`th query on its own by wall clock. A per query CPU
+/// clock read costs a syscall, which would swamp the fastest queries.
+pub fn measure<A: Adapter>(index: &ContigIndex<A>, queries: &[Iv], every: usize) -> Histogram {
+    let mut histogram = Histogram::default();
+    for iv in queries.iter().step_by(every) {
+        let start = Instant::now();
+        hint::black_box(index.find(iv));
+        histogram.record(start.elapsed().as_nanos() as u64);
+    }
+    histogram
+}
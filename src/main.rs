@@ -1,20 +1,47 @@
 #[macro_use]
 extern crate clap;
-use ailist::{self};
-use bio::data_structures::interval_tree::IntervalTree;
+mod adapters;
+mod baselines;
+mod bed;
+mod binning;
+mod cache;
+mod child;
+mod dynamic;
+mod environment;
+mod fuzz;
+mod genome;
+mod iitree;
+mod latency;
+mod mem;
+mod nclist;
+mod output;
+mod perf;
+mod report;
+mod results;
+mod scenario;
+mod sweep;
+mod threads;
+use adapters::{Adapter, ContigIndex, Index};
+use bed::Contigs;
+use cache::IvCache;
 use clap::{App, Arg, ArgMatches, SubCommand};
-use coitree::{COITree, IntervalNode};
 use cpu_time::ProcessTime;
-use iproxy::{self};
-use nested_intervals::IntervalSet;
+use genome::Genome;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
 use rand::Rng;
-use rust_hopper::{self};
-use rust_lapper::{Interval, Lapper};
-use std::ops::Range;
+use rand::SeedableRng;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic;
 use std::time::Duration;
+use threads::Threaded;
 
 arg_enum! {
-    #[derive(PartialEq, Debug)]
+    #[derive(PartialEq, Debug, Clone, Copy)]
     pub enum Lib {
         RustLapper,
         RustHopper,
@@ -23,22 +50,309 @@ arg_enum! {
         NestedInterval,
         COITree,
         AIList,
+        NCList,
+        Binning,
+        IITree,
+        Naive,
+        BinarySearch,
         All,
     }
 }
 
-struct TempInterval {
-    start: u32,
-    stop: u32,
+impl Lib {
+    /// What `All` stands for. The baselines are left out and must be named.
+    const ALL: [Lib; 10] = [
+        Lib::RustLapper,
+        Lib::RustHopper,
+        Lib::RustBio,
+        Lib::IProxy,
+        Lib::NestedInterval,
+        Lib::COITree,
+        Lib::AIList,
+        Lib::NCList,
+        Lib::Binning,
+        Lib::IITree,
+    ];
 }
 
-type Iv = TempInterval;
+/// Evaluate `$body` with `$A` naming the adapter type of `$lib`, which
+/// must already be expanded out of `All`.
+macro_rules! with_lib {
+    ($lib:expr, $A:ident => $body:expr) => {
+        match $lib {
+            Lib::RustLapper => {
+                type $A = adapters::RustLapper;
+                $body
+            }
+            Lib::RustHopper => {
+                type $A = adapters::RustHopper;
+                $body
+            }
+            Lib::RustBio => {
+                type $A = adapters::RustBio;
+                $body
+            }
+            Lib::IProxy => {
+                type $A = adapters::IProxy;
+                $body
+            }
+            Lib::NestedInterval => {
+                type $A = adapters::NestedIntervals;
+                $body
+            }
+            Lib::COITree => {
+                type $A = adapters::CoiTree;
+                $body
+            }
+            Lib::AIList => {
+                type $A = adapters::AIList;
+                $body
+            }
+            Lib::NCList => {
+                type $A = nclist::NCList;
+                $body
+            }
+            Lib::Binning => {
+                type $A = binning::Binning;
+                $body
+            }
+            Lib::IITree => {
+                type $A = iitree::IITree;
+                $body
+            }
+            Lib::Naive => {
+                type $A = baselines::Naive;
+                $body
+            }
+            Lib::BinarySearch => {
+                type $A = baselines::BinarySearch;
+                $body
+            }
+            Lib::All => unreachable!("All is expanded before dispatch"),
+        }
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TempInterval {
+    pub contig: u32,
+    pub start: u32,
+    pub stop: u32,
+}
+
+pub type Iv = TempInterval;
+
+#[cfg(feature = "count_heap")]
+#[global_allocator]
+static ALLOCATOR: mem::Counting = mem::Counting;
+
+/// Everything a library run needs: both sets, the names their contig ids
+/// refer to, and the genome if one was given.
+struct Dataset {
+    contigs: Vec<String>,
+    set_a: Vec<Iv>,
+    set_b: Vec<Iv>,
+    genome: Option<Genome>,
+    window_size: u32,
+    verify: bool,
+    presort: Option<SortOrder>,
+    /// Mixed update and query workload against set B, if asked for.
+    dynamic: Option<dynamic::Mix>,
+    /// Most threads to run the query scaling curve up to.
+    threads: Option<usize>,
+    /// Threads to compare a per-contig parallel build on against one.
+    build_threads: Option<usize>,
+    /// With per-query latencies, the interval between sampled queries.
+    latency: Option<usize>,
+    /// Keys of the workloads to run, or all of them.
+    workloads: Option<Vec<String>>,
+    /// What the random draws were seeded with.
+    seed: u64,
+    /// Limits to run each lib within, in a child process of its own.
+    budget: Option<child::Budget>,
+    /// Run each lib, or each of its workloads, in a child process.
+    isolate: Option<child::Isolate>,
+    /// Seed to shuffle the lib order of every repeat with, if shuffling.
+    order_seed: Option<u64>,
+    /// Settings for the libs that take any.
+    config: adapters::Config,
+}
+
+const STDIN_TWICE: &str = "bed_a and bed_b can't both be read from stdin";
+const STDIN_CHILDREN: &str = "Every child process reads bed_a and bed_b again, so budgets and isolation need files rather than stdin";
+
+/// Key a dataset can be pre-sorted by, within each contig.
+#[derive(Clone, Copy, Debug)]
+enum SortOrder {
+    Start,
+    End,
+}
+
+impl SortOrder {
+    /// The order `--presort` and scenarios call `value`, if any.
+    fn from_name(value: &str) -> Option<SortOrder> {
+        match value {
+            "start" => Some(SortOrder::Start),
+            "end" => Some(SortOrder::End),
+            _ => None,
+        }
+    }
+
+    fn parse(value: &str) -> SortOrder {
+        SortOrder::from_name(value).unwrap_or_else(|| panic!("Unknown sort order: {}", value))
+    }
+
+    fn sort(self, ivs: &mut [Iv]) {
+        match self {
+            SortOrder::Start => ivs.sort_unstable_by_key(|iv| (iv.contig, iv.start, iv.stop)),
+            SortOrder::End => ivs.sort_unstable_by_key(|iv| (iv.contig, iv.stop, iv.start)),
+        }
+    }
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SortOrder::Start => write!(f, "start"),
+            SortOrder::End => write!(f, "end"),
+        }
+    }
+}
+
+impl Dataset {
+    fn new(
+        contigs: Vec<String>,
+        set_a: Vec<Iv>,
+        set_b: Vec<Iv>,
+        genome: Option<Genome>,
+        window_size: u32,
+    ) -> Dataset {
+        Dataset {
+            contigs,
+            set_a,
+            set_b,
+            genome,
+            window_size,
+            verify: false,
+            presort: None,
+            dynamic: None,
+            threads: None,
+            build_threads: None,
+            latency: None,
+            workloads: None,
+            seed: 0,
+            budget: None,
+            isolate: None,
+            order_seed: None,
+            config: adapters::Config::default(),
+        }
+    }
+}
+
+/// Which of the two built indices a workload queries.
+#[derive(Clone, Copy)]
+enum Target {
+    A,
+    B,
+}
+
+/// Keys scenario files pick workloads by, in the order they run.
+const WORKLOAD_KEYS: [&str; 6] = [
+    "self",
+    "cross",
+    "complement_self",
+    "complement_cross",
+    "windows_self",
+    "windows_cross",
+];
+
+/// One timed pass of `queries` over the index built from `target`.
+struct Workload<'a> {
+    key: &'static str,
+    label: &'static str,
+    target: Target,
+    queries: Cow<'a, [Iv]>,
+}
+
+/// What every lib in a run shares, worked out once before the first lib.
+struct Run<'a> {
+    data: &'a Dataset,
+    workloads: Vec<Workload<'a>>,
+    /// Oracle count per workload, with `--verify`.
+    expected: Option<Vec<usize>>,
+    /// Copies of both sets sorted by `--presort`, to build from. Queries
+    /// keep the sets' own order, so sorting doesn't change their timings.
+    sorted: Option<(Vec<Iv>, Vec<Iv>)>,
+    /// The shuffled copies from `Draws`, with `--presort`.
+    unsorted: Option<&'a (Vec<Iv>, Vec<Iv>)>,
+    /// Operations of the dynamic workload, and the oracle's count of their
+    /// query hits with `--verify`.
+    ops: Option<(&'a [dynamic::Op], Option<usize>)>,
+}
+
+/// What a dataset's runs draw from its generator beyond the sets. Drawn
+/// once before the first repeat, so every repeat, and a child running just
+/// one of them, gets the same.
+struct Draws {
+    /// Shuffled copies of both sets, with `--presort`, to time building
+    /// from unsorted input against.
+    unsorted: Option<(Vec<Iv>, Vec<Iv>)>,
+    /// Operations of the dynamic workload.
+    ops: Option<Vec<dynamic::Op>>,
+}
+
+impl Draws {
+    fn new(data: &Dataset) -> Draws {
+        Draws {
+            unsorted: data
+                .presort
+                .map(|_| (shuffled(&data.set_a), shuffled(&data.set_b))),
+            ops: data
+                .dynamic
+                .map(|mix| dynamic::make_ops(&data.set_b, &data.set_a, &mix)),
+        }
+    }
+}
 
 fn main() {
     let matches = App::new("interval_bakeoff")
         .version("0.1")
         .author("Seth Stadick <sstadick@gmail.com>")
         .about("Compare and contrast various Interval Tree/List Libs")
+        .arg(Arg::with_name("bin_min_shift")
+             .long("bin_min_shift")
+             .help("Binning: log2 of the finest bin width. 14 matches tabix, BAI and CSI.")
+             .takes_value(true)
+             .global(true))
+        .arg(Arg::with_name("bin_depth")
+             .long("bin_depth")
+             .help("Binning: number of levels below the root bin. Tabix and BAI use 5, CSI defaults to 6, which covers all of u32. At most 10, so bin ids fit in u32.")
+             .takes_value(true)
+             .global(true))
+        .arg(Arg::with_name("no_linear_index")
+             .long("no_linear_index")
+             .help("Binning: skip the linear index and scan whole bins")
+             .global(true))
+        .arg(Arg::with_name("perf_counters")
+             .long("perf_counters")
+             .alias("perf-counters")
+             .help("Count cycles, instructions, L1d/LLC misses and branch misses around every timed phase with perf_event_open (Linux only), and report per query or per interval averages")
+             .global(true))
+        .arg(Arg::with_name("list_libs")
+             .long("list_libs")
+             .alias("list-libs")
+             .help("List every lib and what it can do natively, then exit"))
+        .arg(Arg::with_name("results")
+             .long("results")
+             .help("Also write every measurement to this file as a tab separated table, for report")
+             .takes_value(true)
+             .global(true))
+        .arg(Arg::with_name("child")
+             .long("child")
+             .help("Run as a child of another run: LIB DATASET REPEAT SEED RESULTS PART")
+             .number_of_values(6)
+             .hidden(true)
+             .global(true))
         .subcommand(SubCommand::with_name("fake")
                     .about("Test the libs on fake data generated internally. Two sets will be created.")
                     .version("0.1")
@@ -52,6 +366,17 @@ fn main() {
                          .long("universe_size")
                          .help("The size of the universe to choose the intervals from.")
                          .takes_value(true))
+                    .arg(Arg::with_name("genome")
+                         .short("g")
+                         .long("genome")
+                         .help("A chrom.sizes file. Intervals are spread over its contigs in proportion to length instead of over the universe.")
+                         .takes_value(true)
+                         .conflicts_with("universe_size"))
+                    .arg(Arg::with_name("window_size")
+                         .long("window_size")
+                         .help("Width of the genome tiling windows used by the genomecov workload")
+                         .takes_value(true)
+                         .requires("genome"))
                     .arg(Arg::with_name("min_interval_size")
                          .long("min_interval_size")
                          .help("The min size of an interval")
@@ -63,17 +388,73 @@ fn main() {
                     .arg(Arg::with_name("add_universe_spanning_interval")
                          .short("a")
                          .long("add_universe_spanning_interval")
-                         .help("Will ad a universe spanning interval to set A"))
+                         .help("Will ad a universe spanning interval to set A, or one per contig with --genome"))
                     .arg(Arg::with_name("save_sets")
                          .short("s")
                          .long("save_sets")
-                         .help("Save the generates sets to the follwing location. Results are bed formatted.")
+                         .help("Save the generates sets to the follwing location prefix, as PREFIX.a.bed and PREFIX.b.bed (or .ivc with --save_format cache).")
+                         .takes_value(true))
+                    .arg(Arg::with_name("save_format")
+                         .long("save_format")
+                         .possible_values(&["bed", "cache"])
+                         .help("Format of the sets written by --save_sets")
+                         .takes_value(true)
+                         .requires("save_sets"))
+                    .arg(Arg::with_name("presort")
+                         .long("presort")
+                         .possible_values(&["start", "end"])
+                         .help("Sort both sets by start or end before running, as a sorted bed would be. Build times from shuffled copies are reported alongside.")
+                         .takes_value(true))
+                    .arg(Arg::with_name("dynamic_ops")
+                         .long("dynamic_ops")
+                         .help("Also run N interleaved inserts, deletes and queries against set B. New intervals and queries are drawn from set A.")
                          .takes_value(true))
+                    .arg(Arg::with_name("dynamic_mix")
+                         .long("dynamic_mix")
+                         .help("Relative weights of the dynamic workload as inserts:deletes:queries [default: 1:1:8]")
+                         .takes_value(true)
+                         .requires("dynamic_ops"))
+                    .arg(Arg::with_name("rebuild_every")
+                         .long("rebuild_every")
+                         .help("Rebuild libs that can't update in place after this many updates. 1 rebuilds on every update. Without it those libs skip the dynamic workload.")
+                         .takes_value(true)
+                         .requires("dynamic_ops"))
+                    .arg(Arg::with_name("threads")
+                         .long("threads")
+                         .help("Also time every workload split across 1 to N threads sharing one index, by wall clock")
+                         .takes_value(true))
+                    .arg(Arg::with_name("build_threads")
+                         .long("build_threads")
+                         .help("Also build both sets with their contigs spread over N threads, and compare wall time and peak memory against one thread")
+                         .takes_value(true))
+                    .arg(Arg::with_name("latency")
+                         .long("latency")
+                         .help("Also time queries one by one and report p50/p99/p999/max latency per lib and workload"))
+                    .arg(Arg::with_name("latency_every")
+                         .long("latency_every")
+                         .help("Only time every Nth query with --latency [default: 1]")
+                         .takes_value(true)
+                         .requires("latency"))
+                    .arg(Arg::with_name("seed")
+                         .long("seed")
+                         .help("Seed every random draw, so the sets and workloads can be made again. Printed when picked at random.")
+                         .takes_value(true))
+                    .arg(output_format_arg())
+                    .arg(time_budget_arg())
+                    .arg(mem_budget_arg())
+                    .arg(isolate_arg())
+                    .arg(repeats_arg())
+                    .arg(shuffle_order_arg())
+                    .arg(order_seed_arg())
+                    .arg(Arg::with_name("verify")
+                         .long("verify")
+                         .help("Check every lib's counts against the naive oracle. Quadratic, so keep N small."))
+                    .arg(exclude_lib_arg())
                     .arg(Arg::with_name("lib")
                          .short("l")
                          .long("lib")
                          .possible_values(&Lib::variants())
-                         .help("Indicate which libs to test one")
+                         .help("Indicate which libs to test one. All skips the Naive and BinarySearch baselines, which must be named.")
                          .takes_value(true)
                          .multiple(true)))
         .subcommand(SubCommand::with_name("real")
@@ -81,498 +462,1618 @@ fn main() {
                     .version("0.1")
                     .arg(Arg::with_name("bed_a")
                          .long("bed_a")
-                         .help("Bed A")
+                         .help("Bed A, or an interval cache made by convert. Use - for stdin; named pipes work too.")
                          .takes_value(true)
                          .required(true))
                     .arg(Arg::with_name("bed_b")
                          .long("bed_b")
-                         .help("Bed B")
+                         .help("Bed B, or an interval cache made by convert. Use - for stdin; named pipes work too.")
                          .takes_value(true)
                          .required(true))
+                    .arg(Arg::with_name("genome")
+                         .short("g")
+                         .long("genome")
+                         .help("A chrom.sizes file. Enables the complement and genomecov workloads.")
+                         .takes_value(true))
+                    .arg(Arg::with_name("window_size")
+                         .long("window_size")
+                         .help("Width of the genome tiling windows used by the genomecov workload")
+                         .takes_value(true)
+                         .requires("genome"))
+                    .arg(Arg::with_name("save_sets")
+                         .short("s")
+                         .long("save_sets")
+                         .help("Save both sets as interval caches at PREFIX.a.ivc and PREFIX.b.ivc for faster reloads.")
+                         .takes_value(true))
+                    .arg(Arg::with_name("presort")
+                         .long("presort")
+                         .possible_values(&["start", "end"])
+                         .help("Sort both sets by start or end before running, as a sorted bed would be. Build times from shuffled copies are reported alongside.")
+                         .takes_value(true))
+                    .arg(Arg::with_name("dynamic_ops")
+                         .long("dynamic_ops")
+                         .help("Also run N interleaved inserts, deletes and queries against set B. New intervals and queries are drawn from set A.")
+                         .takes_value(true))
+                    .arg(Arg::with_name("dynamic_mix")
+                         .long("dynamic_mix")
+                         .help("Relative weights of the dynamic workload as inserts:deletes:queries [default: 1:1:8]")
+                         .takes_value(true)
+                         .requires("dynamic_ops"))
+                    .arg(Arg::with_name("rebuild_every")
+                         .long("rebuild_every")
+                         .help("Rebuild libs that can't update in place after this many updates. 1 rebuilds on every update. Without it those libs skip the dynamic workload.")
+                         .takes_value(true)
+                         .requires("dynamic_ops"))
+                    .arg(Arg::with_name("threads")
+                         .long("threads")
+                         .help("Also time every workload split across 1 to N threads sharing one index, by wall clock")
+                         .takes_value(true))
+                    .arg(Arg::with_name("build_threads")
+                         .long("build_threads")
+                         .help("Also build both sets with their contigs spread over N threads, and compare wall time and peak memory against one thread")
+                         .takes_value(true))
+                    .arg(Arg::with_name("latency")
+                         .long("latency")
+                         .help("Also time queries one by one and report p50/p99/p999/max latency per lib and workload"))
+                    .arg(Arg::with_name("latency_every")
+                         .long("latency_every")
+                         .help("Only time every Nth query with --latency [default: 1]")
+                         .takes_value(true)
+                         .requires("latency"))
+                    .arg(Arg::with_name("seed")
+                         .long("seed")
+                         .help("Seed the shuffles and dynamic workload. Printed when picked at random.")
+                         .takes_value(true))
+                    .arg(output_format_arg())
+                    .arg(time_budget_arg())
+                    .arg(mem_budget_arg())
+                    .arg(isolate_arg())
+                    .arg(repeats_arg())
+                    .arg(shuffle_order_arg())
+                    .arg(order_seed_arg())
+                    .arg(Arg::with_name("verify")
+                         .long("verify")
+                         .help("Check every lib's counts against the naive oracle. Quadratic, so keep N small."))
+                    .arg(exclude_lib_arg())
                     .arg(Arg::with_name("lib")
                          .short("l")
                          .long("lib")
                          .possible_values(&Lib::variants())
-                         .help("Indicate which lib to test one")
+                         .help("Indicate which lib to test one. All skips the Naive and BinarySearch baselines, which must be named.")
                          .takes_value(true)
                          .multiple(true)))
+        .subcommand(SubCommand::with_name("stream")
+                    .about("Index set B, then stream set A through every lib in chunks without ever holding all of it.")
+                    .version("0.1")
+                    .arg(Arg::with_name("bed_a")
+                         .long("bed_a")
+                         .help("Queries: a bed file, interval cache, - for stdin, or a named pipe")
+                         .takes_value(true)
+                         .required(true))
+                    .arg(Arg::with_name("bed_b")
+                         .long("bed_b")
+                         .help("Bed B, or an interval cache made by convert. This side gets indexed.")
+                         .takes_value(true)
+                         .required(true))
+                    .arg(Arg::with_name("chunk_size")
+                         .long("chunk_size")
+                         .help("How many queries to read before handing them to every lib")
+                         .takes_value(true))
+                    .arg(exclude_lib_arg())
+                    .arg(Arg::with_name("lib")
+                         .short("l")
+                         .long("lib")
+                         .possible_values(&Lib::variants())
+                         .help("Indicate which libs to test one. All skips the Naive and BinarySearch baselines, which must be named.")
+                         .takes_value(true)
+                         .multiple(true)))
+        .subcommand(SubCommand::with_name("sweep")
+                    .about("Run the libs over a grid of generated datasets and print one table, to find where they cross over.")
+                    .version("0.1")
+                    .arg(Arg::with_name("num_intervals")
+                         .short("n")
+                         .long("num_intervals")
+                         .help("Intervals per set: a list like 1000,5000 or lo..hi/k for k log-spaced values [default: 1e3..1e5/3]")
+                         .takes_value(true))
+                    .arg(Arg::with_name("density")
+                         .long("density")
+                         .help("Mean number of intervals covering a position, which sets the universe size: a list or lo..hi/k [default: 1]")
+                         .takes_value(true))
+                    .arg(Arg::with_name("min_interval_size")
+                         .long("min_interval_size")
+                         .help("The min size of an interval")
+                         .takes_value(true))
+                    .arg(Arg::with_name("max_interval_size")
+                         .long("max_interval_size")
+                         .help("The max size of an interval: a list or lo..hi/k [default: 80000]")
+                         .takes_value(true))
+                    .arg(Arg::with_name("out")
+                         .short("o")
+                         .long("out")
+                         .help("Write the table here instead of stdout")
+                         .takes_value(true))
+                    .arg(exclude_lib_arg())
+                    .arg(Arg::with_name("lib")
+                         .short("l")
+                         .long("lib")
+                         .possible_values(&Lib::variants())
+                         .help("Indicate which libs to test one. All skips the Naive and BinarySearch baselines, which must be named.")
+                         .takes_value(true)
+                         .multiple(true)))
+        .subcommand(SubCommand::with_name("run")
+                    .about("Run the datasets, workloads and libs a scenario file lists.")
+                    .version("0.1")
+                    .arg(Arg::with_name("scenario")
+                         .long("scenario")
+                         .help("A TOML scenario file")
+                         .takes_value(true)
+                         .required(true))
+                    .arg(output_format_arg()))
+        .subcommand(SubCommand::with_name("fuzz")
+                    .about("Check the libs against the naive oracle on small random and edge case sets, shrinking any disagreement to BED files that reproduce it.")
+                    .version("0.1")
+                    .arg(Arg::with_name("cases")
+                         .long("cases")
+                         .help("Random cases to try after the edge cases [default: 1000]")
+                         .takes_value(true))
+                    .arg(Arg::with_name("seed")
+                         .long("seed")
+                         .help("Seed the random cases. Printed when picked at random.")
+                         .takes_value(true))
+                    .arg(Arg::with_name("out")
+                         .short("o")
+                         .long("out")
+                         .help("Prefix for reproductions, written as PREFIX.LIB.a.bed (queries) and PREFIX.LIB.b.bed (intervals), LIB as --lib names it [default: fuzz]")
+                         .takes_value(true))
+                    .arg(exclude_lib_arg().help("Leave these libs out"))
+                    .arg(Arg::with_name("lib")
+                         .short("l")
+                         .long("lib")
+                         .possible_values(&Lib::variants())
+                         .help("Which libs to check [default: All and BinarySearch]")
+                         .takes_value(true)
+                         .multiple(true)))
+        .subcommand(SubCommand::with_name("report")
+                    .about("Turn --results and sweep tables into one self-contained HTML page of SVG charts.")
+                    .version("0.1")
+                    .arg(Arg::with_name("input")
+                         .short("i")
+                         .long("input")
+                         .help("A table written by --results or sweep")
+                         .takes_value(true)
+                         .multiple(true)
+                         .required(true))
+                    .arg(Arg::with_name("out")
+                         .short("o")
+                         .long("out")
+                         .help("Where to write the HTML [default: stdout]")
+                         .takes_value(true)))
+        .subcommand(SubCommand::with_name("convert")
+                    .about("Convert a bed file into the binary interval cache format.")
+                    .version("0.1")
+                    .arg(Arg::with_name("bed")
+                         .long("bed")
+                         .help("Bed file to convert, or - for stdin")
+                         .takes_value(true)
+                         .required(true))
+                    .arg(Arg::with_name("out")
+                         .short("o")
+                         .long("out")
+                         .help("Where to write the cache")
+                         .takes_value(true)
+                         .required(true)))
         .get_matches();
 
     run(matches);
 }
 
+// Args several subcommands take, made once so they stay alike
+
+fn output_format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("output_format")
+        .long("output_format")
+        .alias("output-format")
+        .help("markdown prints only a lib by workload table of median times, ratios to the fastest lib, memory and checks to stdout, with the usual output moved to stderr")
+        .possible_values(&["text", "markdown"])
+        .default_value("text")
+}
+
+fn time_budget_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("time_budget")
+        .long("time_budget")
+        .alias("time-budget")
+        .help("Run each lib in a child process and stop it if any one build or workload takes longer than this many seconds, recording it as timed out")
+        .takes_value(true)
+}
+
+fn mem_budget_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("mem_budget")
+        .long("mem_budget")
+        .alias("mem-budget")
+        .help("Run each lib in a child process and stop it if it grows by more than this, like 512M or 4G, recording it as out of memory")
+        .takes_value(true)
+}
+
+fn isolate_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("isolate")
+        .long("isolate")
+        .possible_values(&["lib", "workload"])
+        .help("Run each lib, or each lib's builds and each of its workloads, in a fresh child process so no lib runs on a heap another left behind")
+        .takes_value(true)
+}
+
+fn repeats_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("repeats")
+        .long("repeats")
+        .help("Run every lib this many times, a round of all of them per repeat [default: 1]")
+        .takes_value(true)
+}
+
+fn shuffle_order_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("shuffle_order")
+        .long("shuffle_order")
+        .alias("shuffle-order")
+        .help("Run the libs in a new random order every repeat, so warm up and throttling don't always favour the same ones")
+}
+
+fn order_seed_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("order_seed")
+        .long("order_seed")
+        .alias("order-seed")
+        .help("Seed for --shuffle_order [default: the dataset's seed]")
+        .takes_value(true)
+        .requires("shuffle_order")
+}
+
+fn exclude_lib_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("exclude_lib")
+        .long("exclude_lib")
+        .alias("exclude-lib")
+        .possible_values(&Lib::variants())
+        .help("Leave these libs out, typically of All")
+        .takes_value(true)
+        .multiple(true)
+}
+
 fn run(matches: ArgMatches) {
+    if matches.is_present("list_libs") {
+        return list_libs();
+    }
+    match matches.subcommand() {
+        (_, Some(m)) => child::init(m),
+        _ => child::init(&matches),
+    }
+    configure_perf(&matches);
+    // A child's records go to its parent, which does the summing up
+    let markdown = match matches.subcommand() {
+        (_, Some(m)) => m.value_of("output_format") == Some("markdown"),
+        _ => false,
+    } && child::job().is_none();
+    let saved = if markdown {
+        Some(output::divert().expect("Failed to move stdout"))
+    } else {
+        None
+    };
     match matches.subcommand() {
         ("fake", Some(m)) => run_fake(m),
         ("real", Some(m)) => run_real(m),
+        ("stream", Some(m)) => run_stream(m),
+        ("sweep", Some(m)) => run_sweep(m),
+        ("run", Some(m)) => run_scenario(m),
+        ("fuzz", Some(m)) => run_fuzz(m),
+        ("report", Some(m)) => {
+            if let Err(e) = run_report(m) {
+                eprintln!("report: {}", e);
+                std::process::exit(1);
+            }
+        }
+        ("convert", Some(m)) => run_convert(m),
         _ => panic!(),
     }
+    if let Some(saved) = saved {
+        output::restore(saved).expect("Failed to restore stdout");
+        print!("{}", report::markdown(&results::records()));
+    }
+    write_results(&matches);
+}
+
+/// Library settings from the command line, for every index the run builds.
+fn lib_config(matches: &ArgMatches) -> adapters::Config {
+    let min_shift = value_or(matches, "bin_min_shift", 14);
+    let depth = value_or(matches, "bin_depth", 6);
+    let linear = !matches.is_present("no_linear_index");
+    adapters::Config {
+        binning: binning::Scheme::new(min_shift, depth, linear),
+    }
+}
+
+/// Write what the run recorded to `--results`, if given.
+fn write_results(matches: &ArgMatches) {
+    let matches = match matches.subcommand() {
+        (_, Some(m)) => m,
+        _ => matches,
+    };
+    if let Some(path) = matches
+        .value_of("results")
+        .filter(|_| child::job().is_none())
+    {
+        results::write(path, &environment::capture(), &results::records())
+            .expect("Failed to write results");
+    }
+}
+
+fn configure_perf(matches: &ArgMatches) {
+    let matches = match matches.subcommand() {
+        (_, Some(m)) => m,
+        _ => matches,
+    };
+    if matches.is_present("perf_counters") {
+        match perf::enable() {
+            0 => eprintln!("No perf counters could be opened; check perf_event_paranoid"),
+            n => println!("perf counters\t{} opened", n),
+        }
+    }
 }
 
 fn run_fake(matches: &ArgMatches) {
-    let num_intervals = matches
-        .value_of("num_intervals")
-        .unwrap_or("3000000")
-        .parse::<u32>()
-        .unwrap();
-    let universe_size = matches
-        .value_of("universe_size")
-        .unwrap_or("100000000")
-        .parse::<u32>()
-        .unwrap();
-    let min_interval_size = matches
-        .value_of("min_interval_size")
-        .unwrap_or("500")
-        .parse::<u32>()
-        .unwrap();
-    let max_interval_size = matches
-        .value_of("max_interval_size")
-        .unwrap_or("80000")
-        .parse::<u32>()
-        .unwrap();
-    let add_large_span = matches.is_present("add_universe_spanning_interval");
+    let defaults = scenario::Fake::default();
+    let spec = scenario::Fake {
+        num_intervals: value_or(matches, "num_intervals", defaults.num_intervals),
+        universe_size: value_or(matches, "universe_size", defaults.universe_size),
+        genome: matches.value_of("genome").map(String::from),
+        window_size: value_or(matches, "window_size", defaults.window_size),
+        min_interval_size: value_or(matches, "min_interval_size", defaults.min_interval_size),
+        max_interval_size: value_or(matches, "max_interval_size", defaults.max_interval_size),
+        add_universe_spanning_interval: matches.is_present("add_universe_spanning_interval"),
+        seed: matches.value_of("seed").map(|s| s.parse::<u64>().unwrap()),
+        presort: matches.value_of("presort").map(String::from),
+        verify: matches.is_present("verify"),
+        ..defaults
+    };
     let save_sets = matches.value_of("save_sets");
-    let libs: Vec<_> = values_t!(matches.values_of("lib"), Lib).unwrap_or(vec![Lib::All]);
+    let save_format = matches.value_of("save_format").unwrap_or("bed");
+    let dynamic = dynamic_mix(matches);
+    let threads = threads_arg(matches, "threads");
+    let build_threads = threads_arg(matches, "build_threads");
+    let latency = latency_every(matches);
+    let budget = budget(matches);
+    let isolate = matches.value_of("isolate").map(child::Isolate::parse);
+    let repeats = value_or(matches, "repeats", 1);
+    let order_seed = matches
+        .value_of("order_seed")
+        .map(|s| s.parse::<u64>().unwrap());
+    let shuffle_order = matches.is_present("shuffle_order");
+    let libs = selected_libs(matches);
 
+    environment::print();
     println!("Config for fake run:");
-    println!("num_intervals\t{}", num_intervals);
-    println!("universe_size\t{}", universe_size);
-    println!("min_interval_size\t{}", min_interval_size);
-    println!("max_interval_size\t{}", max_interval_size);
-    println!("add_large_span\t{}", add_large_span);
+    println!("num_intervals\t{}", spec.num_intervals);
+    if spec.genome.is_none() {
+        println!("universe_size\t{}", spec.universe_size);
+    }
+    println!("min_interval_size\t{}", spec.min_interval_size);
+    println!("max_interval_size\t{}", spec.max_interval_size);
+    println!("add_large_span\t{}", spec.add_universe_spanning_interval);
+    println!("presort\t{:?}", spec.presort);
+    println!("dynamic\t{:?}", dynamic);
+    println!("threads\t{:?}", threads);
+    println!("build_threads\t{:?}", build_threads);
+    println!("latency\t{:?}", latency);
+    println!("budget\t{:?}", budget);
+    println!("isolate\t{:?}", isolate);
+    println!("repeats\t{}", repeats);
+    println!("shuffle_order\t{}", shuffle_order);
     println!("save_sets\t{:#?}", save_sets);
     println!("libs\t{:#?}", libs);
 
-    let (mut set_a, set_b) = make_intervals(
-        num_intervals,
-        universe_size,
-        min_interval_size,
-        max_interval_size,
-    );
+    let mut data = fake_dataset(&spec);
+    data.dynamic = dynamic;
+    data.threads = threads;
+    data.build_threads = build_threads;
+    data.latency = latency;
+    data.budget = budget;
+    data.isolate = isolate;
+    data.config = lib_config(matches);
+    if shuffle_order {
+        data.order_seed = Some(order_seed.unwrap_or(data.seed));
+    }
 
-    if add_large_span {
-        set_a.push(Iv {
-            start: 0,
-            stop: universe_size,
-        });
+    // Children get the same arguments, but the parent already saved these
+    if let (Some(prefix), None) = (save_sets, child::job()) {
+        save(prefix, save_format, &data).expect("Failed to save sets");
     }
 
-    for lib in libs {
-        match lib {
-            Lib::RustLapper => run_rust_lapper(&set_a, &set_b),
-            Lib::RustHopper => run_rust_hopper(&set_a, &set_b),
-            Lib::RustBio => run_rust_bio(&set_a, &set_b),
-            Lib::IProxy => run_iproxy(&set_a, &set_b),
-            Lib::NestedInterval => run_nested_intervals(&set_a, &set_b),
-            Lib::COITree => run_coitree(&set_a, &set_b),
-            Lib::AIList => run_ailist(&set_a, &set_b),
-            Lib::All => {
-                run_rust_lapper(&set_a, &set_b);
-                run_rust_hopper(&set_a, &set_b);
-                run_rust_bio(&set_a, &set_b);
-                run_iproxy(&set_a, &set_b);
-                run_nested_intervals(&set_a, &set_b);
-                run_coitree(&set_a, &set_b);
-                run_ailist(&set_a, &set_b);
+    run_repeats(&libs, &data, repeats);
+}
+
+/// Parse the value of `name`, or fall back to `default` when it wasn't given.
+fn value_or<T>(matches: &ArgMatches, name: &str, default: T) -> T
+where
+    T: std::str::FromStr,
+    T::Err: fmt::Debug,
+{
+    matches
+        .value_of(name)
+        .map_or(default, |v| v.parse::<T>().unwrap())
+}
+
+/// Seed the generator, then generate both sets of a fake dataset, sorted if
+/// it asks for that.
+fn fake_dataset(spec: &scenario::Fake) -> Dataset {
+    assert!(spec.window_size > 0, "window_size must be greater than 0");
+    let seed = seed_rng(spec.seed);
+    results::set_context(&spec.name, 1);
+    let genome = spec
+        .genome
+        .as_ref()
+        .map(|path| Genome::from_file(path).expect("Failed to read genome file"));
+    let (contigs, mut set_a, set_b) = match genome {
+        Some(ref genome) => {
+            println!(
+                "genome\t{} contigs, {}bp",
+                genome.contigs.len(),
+                genome.total_length()
+            );
+            let set_a = genome::make_random_genome(
+                genome,
+                spec.num_intervals,
+                spec.min_interval_size,
+                spec.max_interval_size,
+            );
+            let set_b = genome::make_random_genome(
+                genome,
+                spec.num_intervals,
+                spec.min_interval_size,
+                spec.max_interval_size,
+            );
+            (genome.names(), set_a, set_b)
+        }
+        None => {
+            let (set_a, set_b) = make_intervals(
+                spec.num_intervals,
+                spec.universe_size,
+                spec.min_interval_size,
+                spec.max_interval_size,
+            );
+            (vec![String::from("universe")], set_a, set_b)
+        }
+    };
+    if spec.add_universe_spanning_interval {
+        match genome {
+            Some(ref genome) => {
+                for (contig, (_, len)) in genome.contigs.iter().enumerate() {
+                    set_a.push(Iv {
+                        contig: contig as u32,
+                        start: 0,
+                        stop: *len,
+                    });
+                }
             }
+            None => set_a.push(Iv {
+                contig: 0,
+                start: 0,
+                stop: spec.universe_size,
+            }),
         }
     }
+    let mut data = Dataset::new(contigs, set_a, set_b, genome, spec.window_size);
+    data.seed = seed;
+    data.verify = spec.verify;
+    data.presort = spec.presort.as_ref().map(|p| SortOrder::parse(p));
+    data
 }
 
-fn run_real(matches: &ArgMatches) {
-    let bed_a = matches.value_of("bed_a").unwrap();
-    let bed_b = matches.value_of("bed_b").unwrap();
-    let libs: Vec<_> = values_t!(matches.values_of("lib"), Lib).unwrap_or(vec![Lib::All]);
-    unimplemented!();
+/// Parse the thread count `name`, if given, which must be at least one.
+fn threads_arg(matches: &ArgMatches, name: &str) -> Option<usize> {
+    let threads = matches.value_of(name).map(|t| t.parse::<usize>().unwrap());
+    assert!(threads != Some(0), "{} must be greater than 0", name);
+    threads
 }
 
-fn run_rust_lapper(set_a: &Vec<Iv>, set_b: &Vec<Iv>) {
-    println!("rust-lapper");
-    let set_a_intervals = set_a
-        .iter()
-        .map(|i| Interval {
-            start: i.start,
-            stop: i.stop,
-            val: 0,
-        })
-        .collect();
-    let set_b_intervals = set_b
-        .iter()
-        .map(|i| Interval {
-            start: i.start,
-            stop: i.stop,
-            val: 0,
-        })
-        .collect();
-    // Object Creation
-    let start = ProcessTime::now();
-    let lapper_a = Lapper::new(set_a_intervals);
-    let elapsed: Duration = start.elapsed();
-    println!("rust-lapper: Time to create set a: {:#?}", elapsed);
-    let start = ProcessTime::now();
-    let lapper_b = Lapper::new(set_b_intervals);
-    let elapsed: Duration = start.elapsed();
-    println!("rust-lapper: Time to create set b: {:#?}", elapsed);
+/// The dynamic workload's settings, if `--dynamic_ops` was given.
+fn dynamic_mix(matches: &ArgMatches) -> Option<dynamic::Mix> {
+    let ops = matches.value_of("dynamic_ops")?.parse::<usize>().unwrap();
+    let rebuild_every = matches
+        .value_of("rebuild_every")
+        .map(|k| k.parse::<usize>().unwrap());
+    Some(dynamic::Mix::new(
+        ops,
+        matches.value_of("dynamic_mix").unwrap_or("1:1:8"),
+        rebuild_every,
+    ))
+}
 
-    // Run A vs A - find
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += lapper_a.find(interval.start, interval.stop).count();
+/// The limits `--time_budget` and `--mem_budget` set, if either was given.
+fn budget(matches: &ArgMatches) -> Option<child::Budget> {
+    make_budget(
+        matches
+            .value_of("time_budget")
+            .map(|t| t.parse::<f64>().unwrap()),
+        matches.value_of("mem_budget"),
+    )
+}
+
+fn make_budget(secs: Option<f64>, bytes: Option<&str>) -> Option<child::Budget> {
+    let bytes =
+        bytes.map(|b| mem::parse_bytes(b).unwrap_or_else(|| panic!("Bad mem_budget: {}", b)));
+    if secs.is_none() && bytes.is_none() {
+        return None;
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "rust-lapper: find 100% hit rate A vs A time/count: {:#?}/{}",
-        elapsed, count
+    assert!(
+        secs.map_or(true, |s| s > 0.0),
+        "time_budget must be greater than 0"
     );
-    // Run A vs A - count
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += lapper_a.count(interval.start, interval.stop);
+    Some(child::Budget { secs, bytes })
+}
+
+/// How often `--latency` samples a query, if it was given.
+fn latency_every(matches: &ArgMatches) -> Option<usize> {
+    if !matches.is_present("latency") {
+        return None;
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "rust-lapper: count 100% hit rate A vs A time/count: {:#?}/{}",
-        elapsed, count
+    let every = matches
+        .value_of("latency_every")
+        .unwrap_or("1")
+        .parse::<usize>()
+        .unwrap();
+    assert!(every > 0, "latency_every must be greater than 0");
+    Some(every)
+}
+
+fn run_real(matches: &ArgMatches) {
+    let spec = scenario::Real {
+        name: String::from("real"),
+        bed_a: matches.value_of("bed_a").unwrap().to_string(),
+        bed_b: matches.value_of("bed_b").unwrap().to_string(),
+        genome: matches.value_of("genome").map(String::from),
+        window_size: value_or(
+            matches,
+            "window_size",
+            scenario::Fake::default().window_size,
+        ),
+        seed: matches.value_of("seed").map(|s| s.parse::<u64>().unwrap()),
+        presort: matches.value_of("presort").map(String::from),
+        verify: matches.is_present("verify"),
+    };
+    let save_sets = matches.value_of("save_sets");
+    let dynamic = dynamic_mix(matches);
+    let threads = threads_arg(matches, "threads");
+    let build_threads = threads_arg(matches, "build_threads");
+    let latency = latency_every(matches);
+    let budget = budget(matches);
+    let isolate = matches.value_of("isolate").map(child::Isolate::parse);
+    let repeats = value_or(matches, "repeats", 1);
+    let order_seed = matches
+        .value_of("order_seed")
+        .map(|s| s.parse::<u64>().unwrap());
+    let shuffle_order = matches.is_present("shuffle_order");
+    let libs = selected_libs(matches);
+
+    environment::print();
+    println!("Config for real run:");
+    println!("bed_a\t{}", spec.bed_a);
+    println!("bed_b\t{}", spec.bed_b);
+    println!("save_sets\t{:#?}", save_sets);
+    println!("presort\t{:?}", spec.presort);
+    println!("dynamic\t{:?}", dynamic);
+    println!("threads\t{:?}", threads);
+    println!("build_threads\t{:?}", build_threads);
+    println!("latency\t{:?}", latency);
+    println!("budget\t{:?}", budget);
+    println!("isolate\t{:?}", isolate);
+    println!("repeats\t{}", repeats);
+    println!("shuffle_order\t{}", shuffle_order);
+    println!("libs\t{:#?}", libs);
+
+    assert!(
+        (budget.is_none() && isolate.is_none()) || (spec.bed_a != "-" && spec.bed_b != "-"),
+        "{}",
+        STDIN_CHILDREN
     );
+    let mut data = real_dataset(&spec);
+    data.dynamic = dynamic;
+    data.threads = threads;
+    data.build_threads = build_threads;
+    data.latency = latency;
+    data.budget = budget;
+    data.isolate = isolate;
+    data.config = lib_config(matches);
+    if shuffle_order {
+        data.order_seed = Some(order_seed.unwrap_or(data.seed));
+    }
 
-    // Run A vs B - find
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += lapper_b.find(interval.start, interval.stop).count();
+    // Children get the same arguments, but the parent already saved these
+    if let (Some(prefix), None) = (save_sets, child::job()) {
+        save(prefix, "cache", &data).expect("Failed to save sets");
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "rust-lapper: find < 100% hit rate A vs B time/count: {:#?}/{}",
-        elapsed, count
-    );
 
-    // Run A vs B - count
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += lapper_b.count(interval.start, interval.stop);
+    run_repeats(&libs, &data, repeats);
+}
+
+/// Load both sets of a real dataset, sorted if it asks for that.
+fn real_dataset(spec: &scenario::Real) -> Dataset {
+    assert!(spec.window_size > 0, "window_size must be greater than 0");
+    assert!(spec.bed_a != "-" || spec.bed_b != "-", "{}", STDIN_TWICE);
+    let seed = seed_rng(spec.seed);
+    results::set_context(&spec.name, 1);
+    let genome = spec
+        .genome
+        .as_ref()
+        .map(|path| Genome::from_file(path).expect("Failed to read genome file"));
+    // Seed the dictionary with the genome so contig ids line up with it
+    let mut contigs = match genome {
+        Some(ref genome) => Contigs::from_names(genome.names()),
+        None => Contigs::default(),
+    };
+    let set_a = load_set(&spec.bed_a, &mut contigs).expect("Failed to read bed_a");
+    let set_b = load_set(&spec.bed_b, &mut contigs).expect("Failed to read bed_b");
+    let mut data = Dataset::new(contigs.into_names(), set_a, set_b, genome, spec.window_size);
+    data.seed = seed;
+    data.verify = spec.verify;
+    data.presort = spec.presort.as_ref().map(|p| SortOrder::parse(p));
+    data
+}
+
+/// Run every dataset of a scenario file `repeats` times, echoing the file
+/// first so the results say what produced them.
+fn run_scenario(matches: &ArgMatches) {
+    let path = matches.value_of("scenario").unwrap();
+    let (scenario, text) = scenario::load(path).expect("Failed to read scenario");
+    // A child's stdout is already its parent's
+    if let (Some(ref file), None) = (&scenario.output, child::job()) {
+        output::redirect_stdout(file).expect("Failed to open scenario output");
+    }
+    let parse = |names: &[String]| -> Vec<Lib> {
+        names
+            .iter()
+            .map(|lib| {
+                lib.parse::<Lib>()
+                    .unwrap_or_else(|_| panic!("Unknown lib in scenario: {}", lib))
+            })
+            .collect()
+    };
+    let libs = match parse(&scenario.libs) {
+        libs if libs.is_empty() => vec![Lib::All],
+        libs => libs,
+    };
+    let libs = expand_libs(&libs, &parse(&scenario.exclude_libs));
+    for key in scenario.workloads.iter() {
+        assert!(
+            WORKLOAD_KEYS.contains(&key.as_str()),
+            "Unknown workload in scenario: {}, expected one of {:?}",
+            key,
+            WORKLOAD_KEYS
+        );
+    }
+    let budget = make_budget(scenario.time_budget, scenario.mem_budget.as_deref());
+    let isolate = scenario.isolate.as_deref().map(child::Isolate::parse);
+
+    environment::print();
+    println!("Scenario {}:", path);
+    println!("{}", text.trim_end());
+    for spec in scenario.datasets.iter() {
+        let name = match spec {
+            scenario::DatasetSpec::Fake(spec) => &spec.name,
+            scenario::DatasetSpec::Real(spec) => &spec.name,
+        };
+        if child::job().is_some_and(|job| job.dataset != *name) {
+            continue;
+        }
+        let mut data = match spec {
+            scenario::DatasetSpec::Fake(spec) => {
+                println!("dataset\t{}", spec.name);
+                fake_dataset(spec)
+            }
+            scenario::DatasetSpec::Real(spec) => {
+                println!("dataset\t{}", spec.name);
+                real_dataset(spec)
+            }
+        };
+        if !scenario.workloads.is_empty() {
+            data.workloads = Some(scenario.workloads.clone());
+        }
+        data.budget = budget;
+        data.isolate = isolate;
+        data.config = lib_config(matches);
+        if scenario.shuffle_order {
+            data.order_seed = Some(scenario.order_seed.unwrap_or(data.seed));
+        }
+        run_repeats(&libs, &data, scenario.repeats);
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "rust-lapper: count < 100% hit rate A vs B time/count: {:#?}/{}",
-        elapsed, count
-    );
 }
 
-fn run_rust_hopper(set_a: &Vec<Iv>, set_b: &Vec<Iv>) {
-    println!("rust-hopper");
-    let set_a_intervals = set_a
-        .iter()
-        .map(|i| rust_hopper::Interval {
-            start: i.start as usize,
-            stop: i.stop as usize,
-            val: 0,
-        })
-        .collect();
-    let set_b_intervals = set_b
+fn run_stream(matches: &ArgMatches) {
+    let bed_a = matches.value_of("bed_a").unwrap();
+    let bed_b = matches.value_of("bed_b").unwrap();
+    let chunk_size = matches
+        .value_of("chunk_size")
+        .unwrap_or("100000")
+        .parse::<usize>()
+        .unwrap();
+    let libs = selected_libs(matches);
+    let config = lib_config(matches);
+    assert!(chunk_size > 0, "chunk_size must be greater than 0");
+    assert!(bed_a != "-" || bed_b != "-", "{}", STDIN_TWICE);
+
+    environment::print();
+    println!("Config for stream run:");
+    println!("bed_a\t{}", bed_a);
+    println!("bed_b\t{}", bed_b);
+    println!("chunk_size\t{}", chunk_size);
+    println!("libs\t{:#?}", libs);
+
+    let mut contigs = Contigs::default();
+    let set_b = load_set(bed_b, &mut contigs).expect("Failed to read bed_b");
+    let num_contigs = contigs.names().len();
+    let indices: Vec<Box<dyn Index>> = libs
         .iter()
-        .map(|i| rust_hopper::Interval {
-            start: i.start as usize,
-            stop: i.stop as usize,
-            val: 0,
-        })
+        .copied()
+        .filter_map(|lib| build_index(lib, &set_b, num_contigs, &config))
         .collect();
-    // Object Creation
-    let start = ProcessTime::now();
-    let hopper_a = rust_hopper::Hopper::new(set_a_intervals);
-    let elapsed: Duration = start.elapsed();
-    println!("rust-hopper: Time to create set a: {:#?}", elapsed);
-    let start = ProcessTime::now();
-    let hopper_b = rust_hopper::Hopper::new(set_b_intervals);
-    let elapsed: Duration = start.elapsed();
-    println!("rust-hopper: Time to create set b: {:#?}", elapsed);
+    drop(set_b);
+    mem::report("after indexing set b");
 
-    // Run A vs A
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += hopper_a
-            .find(interval.start as usize, interval.stop as usize)
-            .count();
+    // A cache is iterated straight off the map; anything else is parsed
+    // line by line. Either way only one chunk of A is ever held.
+    let is_file = bed_a != "-" && fs::metadata(bed_a).expect("Failed to read bed_a").is_file();
+    if is_file && cache::is_cache(bed_a).expect("Failed to read bed_a") {
+        let cache = IvCache::open(bed_a).expect("Failed to read bed_a");
+        let ids: Vec<u32> = cache.contigs.iter().map(|name| contigs.id(name)).collect();
+        let queries = cache.iter().map(|iv| {
+            Ok(Iv {
+                contig: ids[iv.contig as usize],
+                ..iv
+            })
+        });
+        stream_queries(queries, &indices, chunk_size);
+    } else {
+        let reader = bed::open(bed_a).expect("Failed to open bed_a");
+        stream_queries(
+            bed::BedRecords::new(reader, &mut contigs),
+            &indices,
+            chunk_size,
+        );
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "rust-hopper: 100% hit rate A vs A time/count: {:#?}/{}",
-        elapsed, count
-    );
+    mem::report("after streaming set a");
+}
 
-    // Run A vs B
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += hopper_b
-            .find(interval.start as usize, interval.stop as usize)
-            .count();
+/// Hand each chunk of `queries` to every index in turn, timing only the
+/// queries themselves, then report throughput per library.
+fn stream_queries<I>(queries: I, indices: &[Box<dyn Index>], chunk_size: usize)
+where
+    I: Iterator<Item = io::Result<Iv>>,
+{
+    let mut elapsed = vec![Duration::default(); indices.len()];
+    let mut counts = vec![0; indices.len()];
+    let mut total = 0;
+    let mut chunk = Vec::with_capacity(chunk_size);
+    let mut queries = queries.peekable();
+    while queries.peek().is_some() {
+        chunk.clear();
+        for iv in queries.by_ref().take(chunk_size) {
+            chunk.push(iv.expect("Failed to read bed_a"));
+        }
+        total += chunk.len();
+        for (i, index) in indices.iter().enumerate() {
+            let start = ProcessTime::now();
+            counts[i] += index.find_all(&chunk);
+            elapsed[i] += start.elapsed();
+        }
+    }
+
+    for (i, index) in indices.iter().enumerate() {
+        let secs = elapsed[i].as_secs_f64();
+        let rate = if secs > 0.0 { total as f64 / secs } else { 0.0 };
+        println!(
+            "{}: streamed A vs B time/count: {:#?}/{} ({} queries, {:.0} queries/sec)",
+            index.name(),
+            elapsed[i],
+            counts[i],
+            total,
+            rate
+        );
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "rust-hopper: < 100% hit rate A vs B time/count: {:#?}/{}",
-        elapsed, count
-    );
 }
 
-fn run_iproxy(set_a: &Vec<Iv>, set_b: &Vec<Iv>) {
-    println!("IProxy");
-    let set_a_intervals = set_a
-        .iter()
-        .map(|i| iproxy::Interval {
-            start: i.start,
-            stop: i.stop,
-            val: 0,
-        })
-        .collect();
-    let set_b_intervals = set_b
-        .iter()
-        .map(|i| iproxy::Interval {
-            start: i.start,
-            stop: i.stop,
-            val: 0,
-        })
-        .collect();
-    // Object Creation
-    let start = ProcessTime::now();
-    let iproxy_a = iproxy::IProxy::new(set_a_intervals);
-    let elapsed: Duration = start.elapsed();
-    println!("IProxy: Time to create set a: {:#?}", elapsed);
-    let start = ProcessTime::now();
-    let iproxy_b = iproxy::IProxy::new(set_b_intervals);
-    let elapsed: Duration = start.elapsed();
-    println!("IProxy: Time to create set b: {:#?}", elapsed);
+fn build_index(
+    lib: Lib,
+    set_b: &[Iv],
+    num_contigs: usize,
+    config: &adapters::Config,
+) -> Option<Box<dyn Index>> {
+    with_lib!(lib, A => build_boxed::<A>(set_b, num_contigs, config))
+}
 
-    // Run A vs A
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += iproxy_a.find(interval.start, interval.stop).count();
+fn build_boxed<A: Adapter + 'static>(
+    set_b: &[Iv],
+    num_contigs: usize,
+    config: &adapters::Config,
+) -> Option<Box<dyn Index>> {
+    let index = time_build::<A>(set_b, num_contigs, config, "set b")?;
+    Some(Box::new(index))
+}
+
+/// The libs `--lib` and `--exclude_lib` select, with `All` expanded.
+fn selected_libs(matches: &ArgMatches) -> Vec<Lib> {
+    let libs = values_t!(matches.values_of("lib"), Lib).unwrap_or(vec![Lib::All]);
+    let exclude = values_t!(matches.values_of("exclude_lib"), Lib).unwrap_or(vec![]);
+    expand_libs(&libs, &exclude)
+}
+
+/// Replace `All` with every concrete lib, then drop repeats and anything in
+/// `exclude`.
+fn expand_libs(libs: &[Lib], exclude: &[Lib]) -> Vec<Lib> {
+    let mut result = vec![];
+    for &lib in libs {
+        let expanded = match lib {
+            Lib::All => &Lib::ALL[..],
+            _ => std::slice::from_ref(&lib),
+        };
+        for &lib in expanded {
+            if !exclude.contains(&lib) && !result.contains(&lib) {
+                result.push(lib);
+            }
+        }
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "IProxy: 100% hit rate A vs A time/count: {:#?}/{}",
-        elapsed, count
-    );
+    result
+}
 
-    // Run A vs B
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += iproxy_b.find(interval.start, interval.stop).count();
+/// The name `lib`'s adapter prints and records under.
+fn lib_name(lib: Lib) -> &'static str {
+    with_lib!(lib, A => A::NAME)
+}
+
+/// Print what every lib can do natively, for `--list_libs`.
+fn list_libs() {
+    let yes = |b: bool| if b { "yes" } else { "no" };
+    println!("lib\tname\tcount\tseek\tnearest\tinsert\tremove\tthreads\tcoordinates");
+    for &lib in Lib::ALL.iter().chain(&[Lib::Naive, Lib::BinarySearch]) {
+        with_lib!(lib, A => println!(
+            "{:?}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            lib,
+            A::NAME,
+            yes(A::HAS_COUNT),
+            yes(A::HAS_SEEK),
+            yes(A::HAS_NEAREST),
+            yes(A::HAS_INSERT),
+            yes(A::HAS_REMOVE),
+            if A::SHARED { "shared" } else { "replica per thread" },
+            A::COORD
+        ))
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "IProxy: < 100% hit rate A vs B time/count: {:#?}/{}",
-        elapsed, count
-    );
 }
 
-fn run_ailist(set_a: &Vec<Iv>, set_b: &Vec<Iv>) {
-    println!("AIList");
-    let set_a_intervals = set_a
-        .iter()
-        .map(|i| ailist::Interval {
-            start: i.start,
-            end: i.stop,
-            val: 0,
-        })
-        .collect();
-    let set_b_intervals = set_b
-        .iter()
-        .map(|i| ailist::Interval {
-            start: i.start,
-            end: i.stop,
-            val: 0,
-        })
-        .collect();
-    // Object Creation
-    let start = ProcessTime::now();
-    let ailist_a = ailist::AIList::new(set_a_intervals, None);
-    let elapsed: Duration = start.elapsed();
-    println!("AIList: Time to create set a: {:#?}", elapsed);
-    let start = ProcessTime::now();
-    let ailist_b = ailist::AIList::new(set_b_intervals, None);
-    let elapsed: Duration = start.elapsed();
-    println!("AIList: Time to create set b: {:#?}", elapsed);
+fn run_sweep(matches: &ArgMatches) {
+    let num_intervals =
+        sweep::parse_spec(matches.value_of("num_intervals").unwrap_or("1e3..1e5/3"));
+    let densities = sweep::parse_spec(matches.value_of("density").unwrap_or("1"));
+    let min_interval_size = matches
+        .value_of("min_interval_size")
+        .unwrap_or("500")
+        .parse::<u32>()
+        .unwrap();
+    let max_sizes = sweep::parse_spec(matches.value_of("max_interval_size").unwrap_or("80000"));
+    let libs = selected_libs(matches);
+    let config = lib_config(matches);
 
-    // Run A vs A
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += ailist_a.find(interval.start, interval.stop).count();
+    let env = environment::capture();
+    let mut rows = vec![];
+    for &n in num_intervals.iter() {
+        for &density in densities.iter() {
+            for &max in max_sizes.iter() {
+                let point = sweep::Point {
+                    num_intervals: n.round() as u32,
+                    density,
+                    min_interval_size,
+                    max_interval_size: max.round() as u32,
+                };
+                assert!(
+                    point.max_interval_size > min_interval_size,
+                    "max_interval_size must be greater than min_interval_size"
+                );
+                let universe_size = point.universe_size();
+                let set_a = make_random(
+                    point.num_intervals,
+                    universe_size,
+                    min_interval_size,
+                    point.max_interval_size,
+                );
+                let set_b = make_random(
+                    point.num_intervals,
+                    universe_size,
+                    min_interval_size,
+                    point.max_interval_size,
+                );
+                for &lib in libs.iter() {
+                    rows.push(sweep_lib(lib, point, &set_a, &set_b, &config));
+                }
+            }
+        }
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "AIList: 100% hit rate A vs A time/count: {:#?}/{}",
-        elapsed, count
-    );
 
-    // Run A vs B
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += ailist_b.find(interval.start, interval.stop).count();
+    match matches.value_of("out") {
+        Some(path) => sweep::write_table(
+            fs::File::create(path).expect("Failed to create out"),
+            &env,
+            &rows,
+        ),
+        None => sweep::write_table(io::stdout(), &env, &rows),
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "AIList: < 100% hit rate A vs B time/count: {:#?}/{}",
-        elapsed, count
-    );
+    .expect("Failed to write sweep table");
 }
 
-fn run_coitree(set_a: &Vec<Iv>, set_b: &Vec<Iv>) {
-    println!("COITree");
-    let set_a_intervals = set_a
-        .iter()
-        .map(|i| IntervalNode::new(i.start as i32, i.stop as i32, 0))
-        .collect();
-    let set_b_intervals = set_b
-        .iter()
-        .map(|i| IntervalNode::new(i.start as i32, i.stop as i32, 0))
+fn sweep_lib(
+    lib: Lib,
+    point: sweep::Point,
+    set_a: &[Iv],
+    set_b: &[Iv],
+    config: &adapters::Config,
+) -> sweep::Row {
+    with_lib!(lib, A => sweep::measure::<A>(point, set_a, set_b, config))
+}
+
+/// Check every selected lib against the oracle on the edge cases, then on
+/// `--cases` random ones. A lib's first failure is shrunk and written out as
+/// BED files, which `real --verify` reproduces as its A vs B workload. Exits
+/// with an error if any lib failed.
+fn run_fuzz(matches: &ArgMatches) {
+    let cases = value_or(matches, "cases", 1000);
+    let prefix = matches.value_of("out").unwrap_or("fuzz");
+    let libs = match matches.values_of("lib") {
+        Some(_) => selected_libs(matches),
+        None => expand_libs(
+            &[Lib::All, Lib::BinarySearch],
+            &values_t!(matches.values_of("exclude_lib"), Lib).unwrap_or(vec![]),
+        ),
+    };
+    let config = lib_config(matches);
+    seed_rng(matches.value_of("seed").map(|s| s.parse::<u64>().unwrap()));
+    let edge_cases = fuzz::edge_cases();
+    let random: Vec<fuzz::Case> =
+        with_rng(|rng| (0..cases).map(|_| fuzz::random_case(rng)).collect());
+    let contigs: Vec<String> = (0..fuzz::NUM_CONTIGS as u32)
+        .map(fuzz::contig_name)
         .collect();
-    // Object Creation
-    let start = ProcessTime::now();
-    let coitree_a = COITree::new(set_a_intervals);
-    let elapsed: Duration = start.elapsed();
-    println!("COITree: Time to create set a: {:#?}", elapsed);
-    let start = ProcessTime::now();
-    let coitree_b = COITree::new(set_b_intervals);
-    let elapsed: Duration = start.elapsed();
-    println!("COITree: Time to create set b: {:#?}", elapsed);
 
-    // Run A vs A
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += coitree_a
-            .find(interval.start as i32, interval.stop as i32)
-            .count();
+    // Panics are failures to report, not to print mid-run
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut failed = 0;
+    for &lib in libs.iter() {
+        let name = lib_name(lib);
+        let check = |case: &fuzz::Case| with_lib!(lib, A => fuzz::check::<A>(case, &config));
+        let failure = edge_cases
+            .iter()
+            .map(|(label, case)| (label.to_string(), case))
+            .chain(
+                random
+                    .iter()
+                    .enumerate()
+                    .map(|(i, case)| (format!("random case {}", i + 1), case)),
+            )
+            .find_map(|(label, case)| check(case).err().map(|error| (label, case, error)));
+        let (label, case, error) = match failure {
+            Some(failure) => failure,
+            None => {
+                println!(
+                    "{}: ok on {} edge and {} random cases",
+                    name,
+                    edge_cases.len(),
+                    random.len()
+                );
+                continue;
+            }
+        };
+        failed += 1;
+        println!("{}: FAILED on {}: {}", name, label, error);
+        let shrunk = fuzz::shrink(case.clone(), |case| check(case).is_err());
+        // Named as --lib names it, so the replay below works as printed
+        let path = format!("{}.{:?}", prefix, lib);
+        bed::write_bed(&format!("{}.a.bed", path), &shrunk.queries, &contigs)
+            .expect("Failed to write reproduction");
+        bed::write_bed(&format!("{}.b.bed", path), &shrunk.ivs, &contigs)
+            .expect("Failed to write reproduction");
+        println!(
+            "{}: shrunk to {} intervals and {} queries, {}, in {}.a.bed (queries) and {}.b.bed (intervals)",
+            name,
+            shrunk.ivs.len(),
+            shrunk.queries.len(),
+            check(&shrunk).unwrap_err(),
+            path,
+            path
+        );
+        println!(
+            "{}: replay with: real --bed_a {}.a.bed --bed_b {}.b.bed --verify -l {:?}",
+            name, path, path, lib
+        );
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "COITree: 100% hit rate A vs A time/count: {:#?}/{}",
-        elapsed, count
-    );
+    panic::set_hook(hook);
+    if failed > 0 {
+        println!(
+            "{} of {} libs disagreed with the oracle",
+            failed,
+            libs.len()
+        );
+        std::process::exit(1);
+    }
+}
 
-    // Run A vs B
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in set_a.iter() {
-        count += coitree_b
-            .find(interval.start as i32, interval.stop as i32)
-            .count();
+/// Render every input as one HTML report, or say which input couldn't be.
+fn run_report(matches: &ArgMatches) -> Result<(), String> {
+    let mut inputs: Vec<(String, results::Table)> = vec![];
+    for path in matches.values_of("input").unwrap() {
+        let table =
+            results::Table::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        inputs.push((path.to_string(), table));
     }
-    let elapsed: Duration = start.elapsed();
+    let html = report::render(&inputs)?;
+    match matches.value_of("out") {
+        Some(path) => fs::write(path, html).map_err(|e| format!("Failed to write {}: {}", path, e)),
+        None => {
+            print!("{}", html);
+            Ok(())
+        }
+    }
+}
+
+fn run_convert(matches: &ArgMatches) {
+    let bed = matches.value_of("bed").unwrap();
+    let out = matches.value_of("out").unwrap();
+    let mut contigs = Contigs::default();
+    let ivs = bed::read_bed(bed, &mut contigs).expect("Failed to read bed");
+    cache::write_cache(out, &ivs, contigs.names()).expect("Failed to write cache");
     println!(
-        "COITree: < 100% hit rate A vs B time/count: {:#?}/{}",
-        elapsed, count
+        "Wrote {} intervals on {} contigs to {}",
+        ivs.len(),
+        contigs.names().len(),
+        out
     );
 }
 
-fn run_rust_bio(set_a: &Vec<Iv>, set_b: &Vec<Iv>) {
-    println!("rust-bio");
-    let mut set_a_intervals = IntervalTree::new();
-    let mut set_b_intervals = IntervalTree::new();
-    let queries_a: Vec<Range<_>> = set_a.iter().map(|x| x.start..x.stop).collect();
-    // Object Creation
-    let start = ProcessTime::now();
-    set_a
-        .iter()
-        .for_each(|x| set_a_intervals.insert(x.start..x.stop, 0));
-    let elapsed: Duration = start.elapsed();
-    println!("rust-bio: Time to create set a: {:#?}", elapsed);
-    let start = ProcessTime::now();
-    set_b
-        .iter()
-        .for_each(|x| set_b_intervals.insert(x.start..x.stop, 0));
-    let elapsed: Duration = start.elapsed();
-    println!("rust-bio: Time to create set b: {:#?}", elapsed);
+/// Run `libs` on `data` `repeats` times as rounds of every lib, so drift
+/// over the run, from warm up or throttling, spreads over all of them.
+/// Each round's order is printed and recorded, and shuffled anew if the
+/// dataset has an order seed.
+fn run_repeats(libs: &[Lib], data: &Dataset, repeats: usize) {
+    assert!(repeats > 0, "repeats must be greater than 0");
+    let (name, _) = results::context();
+    // Apart from the data's generator, so shuffling leaves the data alone
+    let mut order_rng = data.order_seed.map(StdRng::seed_from_u64);
+    let draws = Draws::new(data);
+    for repeat in 1..=repeats {
+        let mut order = libs.to_vec();
+        if let Some(ref mut rng) = order_rng {
+            order.shuffle(rng);
+        }
+        if child::job().is_some_and(|job| job.repeat != repeat) {
+            continue;
+        }
+        results::set_context(&name, repeat);
+        let names: Vec<&str> = order.iter().map(|&lib| lib_name(lib)).collect();
+        println!("repeat\t{}/{}", repeat, repeats);
+        println!("order\t{}", names.join(", "));
+        if child::job().is_none() {
+            for (i, name) in names.iter().enumerate() {
+                results::record(name, "run", "order", (i + 1) as f64);
+            }
+        }
+        run_libs(&order, data, &draws);
+    }
+}
 
-    // Run A vs A
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in queries_a.iter() {
-        count += set_a_intervals.find(interval).count();
+fn run_libs(libs: &[Lib], data: &Dataset, draws: &Draws) {
+    // A budget needs a child to stop, so it isolates each lib at least
+    let isolate = data.isolate.or(data.budget.map(|_| child::Isolate::Lib));
+    if let (Some(isolate), None) = (isolate, child::job()) {
+        let parts: Vec<Option<&str>> = match isolate {
+            child::Isolate::Lib => vec![None],
+            child::Isolate::Workload => {
+                let mut parts = vec![Some("build")];
+                parts.extend(workloads(data).iter().map(|w| Some(w.key)));
+                if data.dynamic.is_some() {
+                    parts.push(Some("dynamic"));
+                }
+                parts
+            }
+        };
+        let budget = data.budget.unwrap_or_default();
+        for &lib in libs {
+            for &part in parts.iter() {
+                child::run(lib, lib_name(lib), data.seed, budget, part);
+            }
+        }
+        return;
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "rust-bio: 100% hit rate A vs A time/count: {:#?}/{}",
-        elapsed, count
-    );
+    // A child runs the one lib it was given
+    let libs = match child::job() {
+        Some(job) => std::slice::from_ref(&job.lib),
+        None => libs,
+    };
+    let mut workloads = workloads(data);
+    workloads.retain(|w| child::runs(w.key));
+    let expected = if data.verify {
+        Some(expected_counts(data, &workloads))
+    } else {
+        None
+    };
+    let sorted = data.presort.map(|order| {
+        let (mut set_a, mut set_b) = (data.set_a.clone(), data.set_b.clone());
+        order.sort(&mut set_a);
+        order.sort(&mut set_b);
+        (set_a, set_b)
+    });
+    let ops = draws.ops.as_ref().map(|ops| {
+        let expected = if data.verify && child::runs("dynamic") {
+            Some(dynamic::expected_hits(&data.set_b, ops))
+        } else {
+            None
+        };
+        (ops.as_slice(), expected)
+    });
+    let run = Run {
+        data,
+        workloads,
+        expected,
+        sorted,
+        unsorted: draws.unsorted.as_ref(),
+        ops,
+    };
+    for &lib in libs {
+        child::start(data.budget);
+        with_lib!(lib, A => run_lib::<A>(&run));
+    }
+}
 
-    // Run A vs B
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in queries_a.iter() {
-        count += set_b_intervals.find(interval).count();
+/// The query sets every lib is timed on. The genome workloads only exist
+/// when a genome was given.
+fn workloads(data: &Dataset) -> Vec<Workload<'_>> {
+    let mut result = vec![
+        Workload {
+            key: "self",
+            label: "100% hit rate A vs A",
+            target: Target::A,
+            queries: Cow::Borrowed(&data.set_a),
+        },
+        Workload {
+            key: "cross",
+            label: "< 100% hit rate A vs B",
+            target: Target::B,
+            queries: Cow::Borrowed(&data.set_a),
+        },
+    ];
+    if let Some(ref genome) = data.genome {
+        let complement: Vec<Iv> = genome.complement(&data.set_a);
+        let windows: Vec<Iv> = genome.windows(data.window_size);
+        result.push(Workload {
+            key: "complement_self",
+            label: "0% hit rate complement(A) vs A",
+            target: Target::A,
+            queries: Cow::Owned(complement.clone()),
+        });
+        result.push(Workload {
+            key: "complement_cross",
+            label: "complement(A) vs B",
+            target: Target::B,
+            queries: Cow::Owned(complement),
+        });
+        result.push(Workload {
+            key: "windows_self",
+            label: "genomecov windows vs A",
+            target: Target::A,
+            queries: Cow::Owned(windows.clone()),
+        });
+        result.push(Workload {
+            key: "windows_cross",
+            label: "genomecov windows vs B",
+            target: Target::B,
+            queries: Cow::Owned(windows),
+        });
     }
-    let elapsed: Duration = start.elapsed();
-    println!(
-        "rust-bio: < 100% hit rate A vs B time/count: {:#?}/{}",
-        elapsed, count
-    );
+    if let Some(ref keys) = data.workloads {
+        result.retain(|workload| keys.iter().any(|key| key == workload.key));
+    }
+    result
 }
 
-// Cons: querys require mutability
-// Cons: no way to tag an interval
-fn run_nested_intervals(set_a: &Vec<Iv>, set_b: &Vec<Iv>) {
-    println!("nested-intervals");
-    let set_a_nested: Vec<Range<u32>> = set_a.iter().map(|x| x.start..x.stop).collect();
-    let set_b_nested: Vec<Range<u32>> = set_b.iter().map(|x| x.start..x.stop).collect();
-    let queries_a: Vec<Range<_>> = set_a.iter().map(|x| x.start..x.stop).collect();
-    let queries_b: Vec<Range<_>> = set_b.iter().map(|x| x.start..x.stop).collect();
+/// Ground truth for each workload, from the naive scan.
+fn expected_counts(data: &Dataset, workloads: &[Workload]) -> Vec<usize> {
+    let num_contigs = data.contigs.len();
+    let build = |ivs: &[Iv]| {
+        ContigIndex::<baselines::Naive>::build(
+            ContigIndex::<baselines::Naive>::convert(ivs, num_contigs),
+            &data.config,
+        )
+    };
+    let (index_a, index_b) = (build(&data.set_a), build(&data.set_b));
+    workloads
+        .iter()
+        .map(|workload| {
+            let index = match workload.target {
+                Target::A => &index_a,
+                Target::B => &index_b,
+            };
+            workload.queries.iter().map(|iv| index.find(iv)).sum()
+        })
+        .collect()
+}
+
+/// Build an index per contig for both sets, then time every workload
+/// against them, checking counts against the oracle if the run has them.
+/// The dynamic workload, if any, runs last on set B's index.
+fn run_lib<A: Threaded>(run: &Run)
+where
+    A::Item: Send,
+{
+    if child::runs("build") {
+        println!("{}", A::NAME);
+    }
+    let data = run.data;
+    let num_contigs = data.contigs.len();
     // Object Creation
+    let (build_a, build_b) = match run.sorted {
+        Some((ref set_a, ref set_b)) => (set_a, set_b),
+        None => (&data.set_a, &data.set_b),
+    };
+    let (label_a, label_b) = match data.presort {
+        Some(order) => (
+            format!("set a sorted by {}", order),
+            format!("set b sorted by {}", order),
+        ),
+        None => (String::from("set a"), String::from("set b")),
+    };
+    let index_a = time_build::<A>(build_a, num_contigs, &data.config, &label_a);
+    let index_b = time_build::<A>(build_b, num_contigs, &data.config, &label_b);
+    let (index_a, mut index_b) = match (index_a, index_b) {
+        (Some(index_a), Some(index_b)) => (index_a, index_b),
+        // time_build said why
+        _ => return,
+    };
+    if let Some((set_a, set_b)) = run.unsorted.filter(|_| child::runs("build")) {
+        time_build::<A>(set_a, num_contigs, &data.config, "set a unsorted");
+        time_build::<A>(set_b, num_contigs, &data.config, "set b unsorted");
+    }
+    if let Some(build_threads) = data.build_threads.filter(|_| child::runs("build")) {
+        results::step("parallel build");
+        let config = &data.config;
+        threads::parallel_build::<A>(build_a, num_contigs, config, "set a", build_threads);
+        threads::parallel_build::<A>(build_b, num_contigs, config, "set b", build_threads);
+    }
+
+    for (i, workload) in run.workloads.iter().enumerate() {
+        let index = match workload.target {
+            Target::A => &index_a,
+            Target::B => &index_b,
+        };
+        let expected = run.expected.as_ref().map(|e| e[i]);
+        results::step(workload.label);
+        time_queries(index, &workload.queries, workload.label, expected);
+        if let Some(every) = data.latency {
+            report_latency(index, &workload.queries, workload.label, every);
+        }
+    }
+
+    if let Some(max_threads) = data.threads {
+        for workload in run.workloads.iter() {
+            let index = match workload.target {
+                Target::A => &index_a,
+                Target::B => &index_b,
+            };
+            results::step(&format!("threads {}", workload.label));
+            threads::scaling(index, &workload.queries, workload.label, max_threads);
+        }
+    }
+
+    // Last, since it changes set B's index
+    if let (Some((ops, expected)), Some(mix), true) =
+        (run.ops, data.dynamic, child::runs("dynamic"))
+    {
+        // Only rebuild when that was asked for; otherwise it stands in for
+        // updates the lib can't do
+        let rebuild_every = match mix.rebuild_every {
+            Some(k) => Some(k),
+            None if A::HAS_INSERT && A::HAS_REMOVE => Some(1),
+            None => None,
+        };
+        results::step("dynamic");
+        match rebuild_every {
+            Some(k) => dynamic::run(&mut index_b, &data.set_b, num_contigs, ops, k, expected),
+            None => println!(
+                "{}: dynamic skipped: no in place insert and remove, see --rebuild_every",
+                A::NAME
+            ),
+        }
+    }
+}
+
+/// Time converting `ivs` into the library's own interval type, then
+/// building an index from them in the order given, then report both phases
+/// and the end to end cost of going from `&[Iv]` to a queryable index.
+fn time_build<A: Adapter>(
+    ivs: &[Iv],
+    num_contigs: usize,
+    config: &adapters::Config,
+    label: &str,
+) -> Option<ContigIndex<A>> {
+    // The build part of an isolated run already reported this
+    if !child::runs("build") {
+        let items = ContigIndex::<A>::convert(ivs, num_contigs);
+        return ContigIndex::<A>::try_build(items, config).ok();
+    }
+    results::step(label);
+    let tracked = mem::reset_usage_peak();
+    let before = mem::usage();
     let start = ProcessTime::now();
-    let mut set_a_intervals = IntervalSet::new(&set_a_nested).unwrap();
-    let elapsed: Duration = start.elapsed();
-    println!("nested-intervals: Time to create set a: {:#?}", elapsed);
+    let (items, convert_perf) = perf::measure(|| ContigIndex::<A>::convert(ivs, num_contigs));
+    let convert_elapsed: Duration = start.elapsed();
     let start = ProcessTime::now();
-    let mut set_b_intervals = IntervalSet::new(&set_b_nested).unwrap();
-    let elapsed: Duration = start.elapsed();
-    println!("nested-intervals: Time to create set b: {:#?}", elapsed);
+    let (index, build_perf) = perf::measure(|| ContigIndex::<A>::try_build(items, config));
+    let build_elapsed: Duration = start.elapsed();
+    let after = mem::usage();
+    let index = match index {
+        Ok(index) => index,
+        Err(e) => {
+            println!("{}: {} skipped: {}", A::NAME, label, e);
+            return None;
+        }
+    };
+    println!(
+        "{}: Time to convert {}: {:#?}",
+        A::NAME,
+        label,
+        convert_elapsed
+    );
+    println!(
+        "{}: Time to create {}: {:#?}",
+        A::NAME,
+        label,
+        build_elapsed
+    );
+    println!(
+        "{}: Time from Iv to index {}: {:#?}",
+        A::NAME,
+        label,
+        convert_elapsed + build_elapsed
+    );
+    results::record(
+        A::NAME,
+        label,
+        "convert_secs",
+        convert_elapsed.as_secs_f64(),
+    );
+    results::record(A::NAME, label, "build_secs", build_elapsed.as_secs_f64());
+    // The converted items are the index's by now, or freed
+    if let (true, Some((base, _)), Some((current, peak))) = (tracked, before, after) {
+        let (size, peak) = (current.saturating_sub(base), peak.saturating_sub(base));
+        println!(
+            "{}: Index {}/peak {} growth creating {}: {}/{}",
+            A::NAME,
+            mem::USAGE,
+            mem::USAGE,
+            label,
+            mem::human_bytes(size),
+            mem::human_bytes(peak)
+        );
+        results::record(A::NAME, label, "index_bytes", size as f64);
+        results::record(A::NAME, label, "peak_bytes", peak as f64);
+    }
+    let n = ivs.len();
+    perf::report(
+        A::NAME,
+        &format!("convert {}", label),
+        "interval",
+        n,
+        convert_perf,
+    );
+    perf::report(
+        A::NAME,
+        &format!("create {}", label),
+        "interval",
+        n,
+        build_perf,
+    );
+    Some(index)
+}
 
-    // Run A vs A
+fn time_queries<A: Adapter>(
+    index: &ContigIndex<A>,
+    queries: &[Iv],
+    label: &str,
+    expected: Option<usize>,
+) {
     let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in queries_a.iter() {
-        count += set_a_intervals.query_overlapping(interval).iter().count();
-    }
+    let (count, sample) = perf::measure(|| {
+        let mut count = 0;
+        for interval in queries.iter() {
+            count += index.find(interval);
+        }
+        count
+    });
     let elapsed: Duration = start.elapsed();
     println!(
-        "nested-intervals: 100% hit rate A vs A time/count: {:#?}/{}",
-        elapsed, count
+        "{}: {} time/count: {:#?}/{}",
+        A::NAME,
+        label,
+        elapsed,
+        count
     );
+    results::record(A::NAME, label, "query_secs", elapsed.as_secs_f64());
+    results::record(A::NAME, label, "count", count as f64);
+    check_count(A::NAME, label, count, expected);
+    perf::report(A::NAME, label, "query", queries.len(), sample);
 
-    // Run A vs B
-    let start = ProcessTime::now();
-    let mut count = 0;
-    for interval in queries_b.iter() {
-        count += set_b_intervals.query_overlapping(interval).iter().count();
+    if A::HAS_COUNT {
+        let start = ProcessTime::now();
+        let (count, sample) = perf::measure(|| {
+            let mut count = 0;
+            for interval in queries.iter() {
+                count += index.count(interval);
+            }
+            count
+        });
+        let elapsed: Duration = start.elapsed();
+        println!(
+            "{}: count {} time/count: {:#?}/{}",
+            A::NAME,
+            label,
+            elapsed,
+            count
+        );
+        let label = format!("count {}", label);
+        results::record(A::NAME, &label, "query_secs", elapsed.as_secs_f64());
+        results::record(A::NAME, &label, "count", count as f64);
+        check_count(A::NAME, &label, count, expected);
+        perf::report(A::NAME, &label, "query", queries.len(), sample);
     }
-    let elapsed: Duration = start.elapsed();
+
+    if A::HAS_SEEK {
+        // Seeking only pays off in start order; sorting isn't timed
+        let mut sorted = queries.to_vec();
+        sorted.sort_unstable_by_key(|iv| (iv.contig, iv.start));
+        let start = ProcessTime::now();
+        let (count, sample) = perf::measure(|| {
+            let (mut count, mut cursor, mut contig) = (0, 0, None);
+            for interval in sorted.iter() {
+                if contig != Some(interval.contig) {
+                    contig = Some(interval.contig);
+                    cursor = 0;
+                }
+                count += index.seek(interval, &mut cursor);
+            }
+            count
+        });
+        let elapsed: Duration = start.elapsed();
+        println!(
+            "{}: seek {} time/count: {:#?}/{}",
+            A::NAME,
+            label,
+            elapsed,
+            count
+        );
+        let label = format!("seek {}", label);
+        results::record(A::NAME, &label, "query_secs", elapsed.as_secs_f64());
+        results::record(A::NAME, &label, "count", count as f64);
+        check_count(A::NAME, &label, count, expected);
+        perf::report(A::NAME, &label, "query", queries.len(), sample);
+    }
+}
+
+/// A separate pass from `time_queries`, so reading the clock around every
+/// query doesn't inflate the totals.
+fn report_latency<A: Adapter>(index: &ContigIndex<A>, queries: &[Iv], label: &str, every: usize) {
+    let histogram = latency::measure(index, queries, every);
     println!(
-        "nested_intervals: < 100% hit rate A vs B time/count: {:#?}/{}",
-        elapsed, count
+        "{}: {} latency p50/p99/p999/max: {:#?}/{:#?}/{:#?}/{:#?} ({} queries)",
+        A::NAME,
+        label,
+        histogram.percentile(0.5),
+        histogram.percentile(0.99),
+        histogram.percentile(0.999),
+        histogram.max(),
+        histogram.total()
     );
+    for &(metric, q) in [("p50_ns", 0.5), ("p99_ns", 0.99), ("p999_ns", 0.999)].iter() {
+        let nanos = histogram.percentile(q).as_nanos() as f64;
+        results::record(A::NAME, label, metric, nanos);
+    }
+    results::record(A::NAME, label, "max_ns", histogram.max().as_nanos() as f64);
+    // The whole histogram, for the report to draw
+    for (lowest, count) in histogram.buckets() {
+        let metric = format!("latency_bucket_ns {}", lowest);
+        results::record(A::NAME, label, &metric, count as f64);
+    }
+}
+
+fn check_count(name: &str, label: &str, count: usize, expected: Option<usize>) {
+    if let Some(expected) = expected {
+        results::record(name, label, "check", (expected == count) as u8 as f64);
+    }
+    match expected {
+        Some(expected) if expected == count => println!("{}: {} check: ok", name, label),
+        Some(expected) => println!(
+            "{}: {} check: MISMATCH expected {} got {}",
+            name, label, expected, count
+        ),
+        None => (),
+    }
 }
+
 ///// Helpers / Setup functions
 
+thread_local! {
+    // Every random draw goes through here, so one seed repeats a whole run
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Reseed the generator, picking a seed if none was given, and print it so
+/// the run can be repeated. A child always takes its parent's seed, so its
+/// data is the parent's.
+fn seed_rng(seed: Option<u64>) -> u64 {
+    let seed = child::job()
+        .map(|job| job.seed)
+        .or(seed)
+        .unwrap_or_else(rand::random);
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+    println!("seed\t{}", seed);
+    seed
+}
+
+pub fn with_rng<T, F: FnOnce(&mut StdRng) -> T>(f: F) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+fn shuffled(ivs: &[Iv]) -> Vec<Iv> {
+    let mut result = ivs.to_vec();
+    with_rng(|rng| result.shuffle(rng));
+    result
+}
+
+/// Load a set from either a BED file or an interval cache, translating its
+/// contig ids into `contigs`. Only regular files are sniffed for the cache
+/// magic, since peeking at stdin or a FIFO would consume it.
+fn load_set(path: &str, contigs: &mut Contigs) -> io::Result<Vec<Iv>> {
+    if path == "-" || !fs::metadata(path)?.is_file() || !cache::is_cache(path)? {
+        return bed::read_bed(path, contigs);
+    }
+    let cache = IvCache::open(path)?;
+    let ids: Vec<u32> = cache.contigs.iter().map(|name| contigs.id(name)).collect();
+    let mut ivs: Vec<(u32, Iv)> = cache
+        .indexed()
+        .map(|(i, iv)| {
+            let contig = ids[iv.contig as usize];
+            (i, Iv { contig, ..iv })
+        })
+        .collect();
+    // Back in the order of the set the cache was written from
+    ivs.sort_by_key(|&(i, _)| i);
+    Ok(ivs.into_iter().map(|(_, iv)| iv).collect())
+}
+
+/// Write both sets of `data` next to each other at `prefix`.
+fn save(prefix: &str, format: &str, data: &Dataset) -> io::Result<()> {
+    let sets = [("a", &data.set_a), ("b", &data.set_b)];
+    for (name, set) in sets.iter() {
+        match format {
+            "cache" => cache::write_cache(&format!("{}.{}.ivc", prefix, name), set, &data.contigs)?,
+            _ => bed::write_bed(&format!("{}.{}.bed", prefix, name), set, &data.contigs)?,
+        }
+    }
+    Ok(())
+}
+
 fn randomi(imin: u32, imax: u32) -> u32 {
-    let mut rng = rand::thread_rng();
-    imin + rng.gen_range(0, imax - imin)
+    imin + with_rng(|rng| rng.gen_range(0, imax - imin))
 }
 
 fn make_random(n: u32, range_max: u32, size_min: u32, size_max: u32) -> Vec<Iv> {
     let mut result = Vec::with_capacity(n as usize);
-    for i in 0..n {
+    for _ in 0..n {
         let s = randomi(0, range_max);
         let e = s + randomi(size_min, size_max);
-        result.push(Iv { start: s, stop: e });
+        result.push(Iv {
+            contig: 0,
+            start: s,
+            stop: e,
+        });
     }
     result
 }
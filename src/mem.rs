@@ -0,0 +1,204 @@
+#[cfg(feature = "count_heap")]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+#[cfg(feature = "count_heap")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The system allocator, counting live heap bytes and their peak. RSS can't
+/// tell an index from pages kept after whatever ran before it; this can,
+/// at the cost of two atomic updates per allocation, so it is only built
+/// with the `count_heap` feature.
+#[cfg(feature = "count_heap")]
+pub struct Counting;
+
+#[cfg(feature = "count_heap")]
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "count_heap")]
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+/// Live heap bytes allocations may not take the total past.
+#[cfg(feature = "count_heap")]
+static LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// False if `bytes` more would go over the limit, in which case the
+/// allocation fails and the process aborts.
+#[cfg(feature = "count_heap")]
+fn fits(bytes: usize) -> bool {
+    ALLOCATED.load(Ordering::Relaxed).saturating_add(bytes) <= LIMIT.load(Ordering::Relaxed)
+}
+
+#[cfg(feature = "count_heap")]
+fn grow(bytes: usize) {
+    let now = ALLOCATED.fetch_add(bytes, Ordering::Relaxed) + bytes;
+    PEAK.fetch_max(now, Ordering::Relaxed);
+}
+
+#[cfg(feature = "count_heap")]
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if !fits(layout.size()) {
+            return std::ptr::null_mut();
+        }
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if !fits(layout.size()) {
+            return std::ptr::null_mut();
+        }
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if !fits(new_size.saturating_sub(layout.size())) {
+            return std::ptr::null_mut();
+        }
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+            grow(new_size);
+        }
+        new
+    }
+}
+
+/// What `usage` measures: the heap with the `count_heap` feature, RSS
+/// otherwise.
+#[cfg(feature = "count_heap")]
+pub const USAGE: &str = "heap";
+#[cfg(not(feature = "count_heap"))]
+pub const USAGE: &str = "rss";
+
+/// Bytes in use as `(current, peak)`, where the peak is since the last
+/// `reset_usage_peak`, or `None` where the platform doesn't expose them.
+#[cfg(feature = "count_heap")]
+pub fn usage() -> Option<(u64, u64)> {
+    Some((
+        ALLOCATED.load(Ordering::Relaxed) as u64,
+        PEAK.load(Ordering::Relaxed) as u64,
+    ))
+}
+
+#[cfg(not(feature = "count_heap"))]
+pub fn usage() -> Option<(u64, u64)> {
+    rss()
+}
+
+/// Start the peak `usage` reports over from now. Returns false where that
+/// isn't possible, in which case the peak covers the whole run.
+#[cfg(feature = "count_heap")]
+pub fn reset_usage_peak() -> bool {
+    PEAK.store(ALLOCATED.load(Ordering::Relaxed), Ordering::Relaxed);
+    true
+}
+
+#[cfg(not(feature = "count_heap"))]
+pub fn reset_usage_peak() -> bool {
+    reset_peak()
+}
+
+/// Make allocations fail once the process has grown by `bytes` from now:
+/// live heap bytes with the `count_heap` feature, and address space, which
+/// also counts reserved but untouched pages, otherwise.
+#[cfg(feature = "count_heap")]
+pub fn limit_growth(bytes: usize) {
+    let limit = ALLOCATED.load(Ordering::Relaxed).saturating_add(bytes);
+    LIMIT.store(limit, Ordering::Relaxed);
+}
+
+#[cfg(all(unix, not(feature = "count_heap")))]
+pub fn limit_growth(bytes: usize) {
+    let size = status("VmSize:").expect("Failed to read the address space size");
+    let limit = size.saturating_add(bytes as u64) as libc::rlim_t;
+    let rlimit = libc::rlimit {
+        rlim_cur: limit,
+        rlim_max: limit,
+    };
+    // Safe: setrlimit only reads the struct passed to it
+    if unsafe { libc::setrlimit(libc::RLIMIT_AS, &rlimit) } != 0 {
+        panic!(
+            "Failed to limit memory: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(any(unix, feature = "count_heap")))]
+pub fn limit_growth(_bytes: usize) {
+    panic!("mem_budget needs the count_heap feature on this platform");
+}
+
+/// Parse a byte count with an optional binary K, M, G or T suffix, as in
+/// `512M` or `4G`.
+pub fn parse_bytes(value: &str) -> Option<usize> {
+    let value = value.trim();
+    let (number, shift) = match value.chars().last()?.to_ascii_uppercase() {
+        'K' => (&value[..value.len() - 1], 10),
+        'M' => (&value[..value.len() - 1], 20),
+        'G' => (&value[..value.len() - 1], 30),
+        'T' => (&value[..value.len() - 1], 40),
+        _ => (value, 0),
+    };
+    let number = number.parse::<f64>().ok()?;
+    Some((number * (1u64 << shift) as f64) as usize)
+}
+
+/// Resident set size of this process in bytes, as `(current, peak)`. Only
+/// available on Linux, where it is read from `/proc/self/status`.
+pub fn rss() -> Option<(u64, u64)> {
+    Some((status("VmRSS:")?, status("VmHWM:")?))
+}
+
+/// A size field of `/proc/self/status`, in bytes.
+fn status(name: &str) -> Option<u64> {
+    fs::read_to_string("/proc/self/status")
+        .ok()?
+        .lines()
+        .find(|line| line.starts_with(name))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+}
+
+/// Start the peak RSS over from the current RSS, so the next `rss` reports
+/// the peak since this call. Linux only; returns false where it isn't
+/// possible, in which case the peak covers the whole run.
+pub fn reset_peak() -> bool {
+    fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+/// Print the current and peak RSS with a label, or nothing where the
+/// platform doesn't expose them.
+pub fn report(label: &str) {
+    if let Some((current, peak)) = rss() {
+        println!(
+            "{}: rss/peak rss: {}/{}",
+            label,
+            human_bytes(current),
+            human_bytes(peak)
+        );
+    }
+}
+
+pub fn human_bytes(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", value, units[unit])
+}
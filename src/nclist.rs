@@ -0,0 +1,119 @@
+use crate::adapters::{Adapter, Config};
+use crate::Iv;
+
+/// A nested containment list (Alekseyenko & Lee, 2007) with an immutable
+/// query. Intervals contained in another are moved into that interval's
+/// sublist, which leaves every list with strictly increasing starts *and*
+/// stops, so the first overlap in a list can be binary searched by stop.
+///
+/// All lists live in one flat array, laid out breadth first: the top level
+/// list is `0..top_len`, and the sublist of the interval at `i` is
+/// `sub_start[i]..sub_start[i] + sub_len[i]`.
+pub struct NCList {
+    starts: Vec<u32>,
+    stops: Vec<u32>,
+    sub_start: Vec<u32>,
+    sub_len: Vec<u32>,
+    top_len: usize,
+}
+
+impl NCList {
+    pub fn new(mut ivs: Vec<(u32, u32)>) -> Self {
+        // Start ascending, stop descending, so containers precede what
+        // they contain
+        ivs.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+        // The stack holds the chain of intervals containing the current one
+        let mut top = vec![];
+        let mut children: Vec<Vec<usize>> = vec![vec![]; ivs.len()];
+        let mut stack: Vec<usize> = vec![];
+        for (i, iv) in ivs.iter().enumerate() {
+            while let Some(&parent) = stack.last() {
+                if ivs[parent].1 < iv.1 {
+                    stack.pop();
+                } else {
+                    break;
+                }
+            }
+            match stack.last() {
+                Some(&parent) => children[parent].push(i),
+                None => top.push(i),
+            }
+            stack.push(i);
+        }
+
+        let top_len = top.len();
+        let mut order = top;
+        let mut sub_start = Vec::with_capacity(ivs.len());
+        let mut sub_len = Vec::with_capacity(ivs.len());
+        let mut i = 0;
+        while i < order.len() {
+            let kids = &children[order[i]];
+            sub_start.push(order.len() as u32);
+            sub_len.push(kids.len() as u32);
+            order.extend_from_slice(kids);
+            i += 1;
+        }
+
+        NCList {
+            starts: order.iter().map(|&i| ivs[i].0).collect(),
+            stops: order.iter().map(|&i| ivs[i].1).collect(),
+            sub_start,
+            sub_len,
+            top_len,
+        }
+    }
+
+    /// Overlaps in the top level list and, for every overlapping member,
+    /// in its sublist. Lists still to search are kept on a stack rather
+    /// than recursed into, since duplicates nest in each other and the
+    /// nesting can be as deep as the set is large.
+    pub fn count(&self, start: u32, stop: u32) -> usize {
+        let mut count = 0;
+        let mut lists: Vec<(usize, usize)> = vec![(0, self.top_len)];
+        while let Some((lo, hi)) = lists.pop() {
+            let mut i = lo + first_stop_after(&self.stops[lo..hi], start);
+            while i < hi && self.starts[i] < stop {
+                count += 1;
+                if self.sub_len[i] > 0 {
+                    let sub = self.sub_start[i] as usize;
+                    lists.push((sub, sub + self.sub_len[i] as usize));
+                }
+                i += 1;
+            }
+        }
+        count
+    }
+}
+
+/// Index of the first stop strictly greater than `start`.
+fn first_stop_after(stops: &[u32], start: u32) -> usize {
+    let mut low = 0;
+    let mut high = stops.len();
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if stops[mid] <= start {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+impl Adapter for NCList {
+    const NAME: &'static str = "nclist";
+    type Item = (u32, u32);
+
+    fn convert(ivs: &[Iv]) -> Vec<Self::Item> {
+        ivs.iter().map(|iv| (iv.start, iv.stop)).collect()
+    }
+
+    fn build(items: Vec<Self::Item>, _config: &Config) -> Self {
+        NCList::new(items)
+    }
+
+    fn find(&self, start: u32, stop: u32) -> usize {
+        self.count(start, stop)
+    }
+}
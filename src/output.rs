@@ -0,0 +1,81 @@
+//! Where stdout goes. Everything is printed with `println!`, so moving the
+//! file descriptor underneath moves all of it.
+
+use std::fs;
+use std::io::{self, Write};
+
+/// Point this process's stdout at `path`, so everything printed from here
+/// on lands in the file.
+#[cfg(unix)]
+pub fn redirect_stdout(path: &str) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    io::stdout().flush()?;
+    let file = fs::File::create(path)?;
+    if unsafe { libc::dup2(file.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn redirect_stdout(_path: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "output is only supported on unix",
+    ))
+}
+
+/// Stdout as it was before `divert` or `silence`, to go back to.
+pub struct Saved(#[cfg(unix)] libc::c_int);
+
+/// Send stdout to stderr until `restore`, so a summary printed afterwards is
+/// all stdout gets. Elsewhere nothing moves and the summary follows the log.
+#[cfg(unix)]
+pub fn divert() -> io::Result<Saved> {
+    swap(libc::STDERR_FILENO)
+}
+
+#[cfg(not(unix))]
+pub fn divert() -> io::Result<Saved> {
+    Ok(Saved())
+}
+
+/// Discard stdout until `restore`.
+#[cfg(unix)]
+pub fn silence() -> io::Result<Saved> {
+    use std::os::unix::io::AsRawFd;
+    let null = fs::OpenOptions::new().write(true).open("/dev/null")?;
+    swap(null.as_raw_fd())
+}
+
+#[cfg(not(unix))]
+pub fn silence() -> io::Result<Saved> {
+    Ok(Saved())
+}
+
+/// Point stdout at `fd`, keeping a copy of where it pointed before.
+#[cfg(unix)]
+fn swap(fd: libc::c_int) -> io::Result<Saved> {
+    io::stdout().flush()?;
+    let saved = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if saved < 0 || unsafe { libc::dup2(fd, libc::STDOUT_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Saved(saved))
+}
+
+#[cfg(unix)]
+pub fn restore(saved: Saved) -> io::Result<()> {
+    io::stdout().flush()?;
+    let result = unsafe { libc::dup2(saved.0, libc::STDOUT_FILENO) };
+    unsafe { libc::close(saved.0) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn restore(_saved: Saved) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use std::cell::RefCell;
+use std::fs::File;
+
+/// Hardware events counted around each timed phase, as `(name, type,
+/// config)` for `perf_event_open`. Cache events are read misses.
+const EVENTS: [(&str, u32, u64); 5] = [
+    ("cycles", sys::HARDWARE, sys::CPU_CYCLES),
+    ("instructions", sys::HARDWARE, sys::INSTRUCTIONS),
+    ("L1d misses", sys::HW_CACHE, sys::L1D_READ_MISS),
+    ("LLC misses", sys::HW_CACHE, sys::LL_READ_MISS),
+    ("branch misses", sys::HARDWARE, sys::BRANCH_MISSES),
+];
+
+thread_local! {
+    // Counters follow the thread that opened them, so each thread has its own
+    static COUNTERS: RefCell<Vec<(&'static str, File)>> = const { RefCell::new(vec![]) };
+}
+
+/// Event totals for one measured phase, scaled up for any time the kernel
+/// had the counter multiplexed out.
+pub type Sample = Vec<(&'static str, f64)>;
+
+/// Open a counter for each of `EVENTS` on this thread, counting user space
+/// only. Events the CPU or kernel won't give us, as is common in VMs or with
+/// a strict `perf_event_paranoid`, are left out. Returns how many opened.
+pub fn enable() -> usize {
+    COUNTERS.with(|counters| {
+        let mut counters = counters.borrow_mut();
+        counters.clear();
+        for &(name, type_, config) in EVENTS.iter() {
+            if let Some(file) = sys::open(type_, config) {
+                counters.push((name, file));
+            }
+        }
+        counters.len()
+    })
+}
+
+/// Run `f` between resetting and reading every open counter. Without any
+/// counters this is just `f`.
+pub fn measure<T, F: FnOnce() -> T>(f: F) -> (T, Option<Sample>) {
+    COUNTERS.with(|counters| {
+        let counters = counters.borrow();
+        if counters.is_empty() {
+            return (f(), None);
+        }
+        for (_, file) in counters.iter() {
+            sys::start(file);
+        }
+        let result = f();
+        for (_, file) in counters.iter() {
+            sys::stop(file);
+        }
+        let sample = counters
+            .iter()
+            .map(|(name, file)| (*name, sys::read(file)))
+            .collect();
+        (result, Some(sample))
+    })
+}
+
+/// Print `sample` averaged over `n` units, such as queries or intervals.
+pub fn report(name: &str, label: &str, unit: &str, n: usize, sample: Option<Sample>) {
+    let sample = match sample {
+        Some(sample) => sample,
+        None => return,
+    };
+    let n = n.max(1) as f64;
+    let averages: Vec<String> = sample
+        .iter()
+        .map(|(event, total)| format!("{} {:.1}", event, total / n))
+        .collect();
+    println!(
+        "{}: {} perf per {}: {}",
+        name,
+        label,
+        unit,
+        averages.join(", ")
+    );
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use perf_event_open_sys::bindings::{self, perf_event_attr};
+    use perf_event_open_sys::{ioctls, perf_event_open};
+    use std::fs::File;
+    use std::io::Read;
+    use std::mem;
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    pub const HARDWARE: u32 = bindings::perf_type_id_PERF_TYPE_HARDWARE;
+    pub const HW_CACHE: u32 = bindings::perf_type_id_PERF_TYPE_HW_CACHE;
+    pub const CPU_CYCLES: u64 = bindings::perf_hw_id_PERF_COUNT_HW_CPU_CYCLES as u64;
+    pub const INSTRUCTIONS: u64 = bindings::perf_hw_id_PERF_COUNT_HW_INSTRUCTIONS as u64;
+    pub const BRANCH_MISSES: u64 = bindings::perf_hw_id_PERF_COUNT_HW_BRANCH_MISSES as u64;
+    pub const L1D_READ_MISS: u64 = cache_miss(bindings::perf_hw_cache_id_PERF_COUNT_HW_CACHE_L1D);
+    pub const LL_READ_MISS: u64 = cache_miss(bindings::perf_hw_cache_id_PERF_COUNT_HW_CACHE_LL);
+
+    /// Cache event configs are `id | op << 8 | result << 16`.
+    const fn cache_miss(id: u32) -> u64 {
+        id as u64
+            | (bindings::perf_hw_cache_op_id_PERF_COUNT_HW_CACHE_OP_READ as u64) << 8
+            | (bindings::perf_hw_cache_op_result_id_PERF_COUNT_HW_CACHE_RESULT_MISS as u64) << 16
+    }
+
+    pub fn open(type_: u32, config: u64) -> Option<File> {
+        let mut attr = perf_event_attr {
+            size: mem::size_of::<perf_event_attr>() as u32,
+            type_,
+            config,
+            read_format: (bindings::perf_event_read_format_PERF_FORMAT_TOTAL_TIME_ENABLED
+                | bindings::perf_event_read_format_PERF_FORMAT_TOTAL_TIME_RUNNING)
+                as u64,
+            ..Default::default()
+        };
+        attr.set_disabled(1);
+        attr.set_exclude_kernel(1);
+        attr.set_exclude_hv(1);
+        // This thread, on any CPU
+        let fd = unsafe { perf_event_open(&mut attr, 0, -1, -1, 0) };
+        if fd < 0 {
+            None
+        } else {
+            Some(unsafe { File::from_raw_fd(fd) })
+        }
+    }
+
+    pub fn start(file: &File) {
+        unsafe {
+            ioctls::RESET(file.as_raw_fd(), 0);
+            ioctls::ENABLE(file.as_raw_fd(), 0);
+        }
+    }
+
+    pub fn stop(file: &File) {
+        unsafe {
+            ioctls::DISABLE(file.as_raw_fd(), 0);
+        }
+    }
+
+    /// The count, extrapolated to the whole time the counter was enabled.
+    pub fn read(mut file: &File) -> f64 {
+        let mut buf = [0u8; 24];
+        if file.read_exact(&mut buf).is_err() {
+            return 0.0;
+        }
+        let word = |i: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&buf[i * 8..i * 8 + 8]);
+            u64::from_ne_bytes(bytes) as f64
+        };
+        let (value, enabled, running) = (word(0), word(1), word(2));
+        if running > 0.0 {
+            value * enabled / running
+        } else {
+            0.0
+        }
+    }
+}
+
+// Elsewhere nothing opens, so every measurement is just the closure
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::fs::File;
+
+    pub const HARDWARE: u32 = 0;
+    pub const HW_CACHE: u32 = 0;
+    pub const CPU_CYCLES: u64 = 0;
+    pub const INSTRUCTIONS: u64 = 0;
+    pub const BRANCH_MISSES: u64 = 0;
+    pub const L1D_READ_MISS: u64 = 0;
+    pub const LL_READ_MISS: u64 = 0;
+
+    pub fn open(_type: u32, _config: u64) -> Option<File> {
+        None
+    }
+
+    pub fn start(_file: &File) {}
+
+    pub fn stop(_file: &File) {}
+
+    pub fn read(_file: &File) -> f64 {
+        0.0
+    }
+}
@@ -0,0 +1,689 @@
+use crate::mem;
+use crate::results::{median, Record, Table};
+use crate::sweep;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Colours given to libs in order of first appearance, so a lib keeps its
+/// colour across every chart of a report.
+const PALETTE: [&str; 12] = [
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+    "#9c755f", "#bab0ac", "#17becf", "#8c564b",
+];
+
+/// Metrics a parent records when a lib's child process stopped early, with
+/// how to say so.
+const STOPPED: [(&str, &str); 3] = [
+    ("timed_out", "timed out"),
+    ("out_of_memory", "out of memory"),
+    ("failed", "failed"),
+];
+
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 360.0;
+/// Plot margins: left, right (room for the legend), top, bottom.
+const MARGIN: (f64, f64, f64, f64) = (80.0, 170.0, 30.0, 50.0);
+
+/// Render `inputs`, each a `--results` or `sweep` table with the path it was
+/// read from, as one HTML page with inline SVG and no scripts. Fails if an
+/// input is neither.
+pub fn render(inputs: &[(String, Table)]) -> Result<String, String> {
+    let mut records = vec![];
+    let mut sweeps = vec![];
+    let runs = inputs.iter().filter(|(_, t)| t.records().is_some()).count();
+    for (path, table) in inputs {
+        match table.records() {
+            // Runs are kept apart even where their datasets share a name
+            Some(r) if runs > 1 => records.extend(r.into_iter().map(|r| Record {
+                dataset: format!("{} ({})", r.dataset, path),
+                ..r
+            })),
+            Some(r) => records.extend(r),
+            None if table.header.join("\t") == sweep::Row::HEADER => sweeps.push(table),
+            None => return Err(format!("{} is neither a --results nor a sweep table", path)),
+        }
+    }
+    let mut libs: Vec<&str> = unique(records.iter().map(|r| r.lib.as_str()));
+    for table in sweeps.iter() {
+        let lib = table.column("lib").unwrap();
+        libs = unique(
+            libs.into_iter()
+                .chain(table.rows.iter().map(|r| r[lib].as_str())),
+        );
+    }
+    let colour = |lib: &str| {
+        let i = libs.iter().position(|l| *l == lib).unwrap_or(0);
+        PALETTE[i % PALETTE.len()]
+    };
+
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>interval_bakeoff report</title>\n\
+         <style>body{font-family:sans-serif;max-width:760px;margin:2em auto;color:#222}\
+         svg{display:block;margin:1em 0}table{border-collapse:collapse;font-size:small}\
+         td{padding:0 1em 0 0;vertical-align:top}h2{border-bottom:1px solid #ccc}</style>\n\
+         </head><body>\n<h1>interval_bakeoff report</h1>\n",
+    );
+    for (path, table) in inputs {
+        let _ = write!(html, "<details><summary>{}</summary><table>", escape(path));
+        for (key, value) in table.env.iter() {
+            let _ = write!(
+                html,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                escape(key),
+                escape(value)
+            );
+        }
+        html += "</table></details>\n";
+    }
+
+    for dataset in unique(records.iter().map(|r| r.dataset.as_str())) {
+        let rows: Vec<&Record> = records.iter().filter(|r| r.dataset == dataset).collect();
+        let _ = writeln!(html, "<h2>{}</h2>", escape(dataset));
+        dataset_charts(&mut html, &rows, &colour);
+    }
+    for table in sweeps {
+        html += "<h2>Sweep</h2>\n";
+        sweep_charts(&mut html, table, &colour);
+    }
+    html += "</body></html>\n";
+    Ok(html)
+}
+
+/// A markdown table per dataset, a row per lib: median build and query
+/// times with their ratio to the fastest lib that got the counts right, set
+/// B's index size, and whether the lib's counts matched the oracle.
+/// Libs that were stopped early say so where their numbers would be.
+pub fn markdown(records: &[Record]) -> String {
+    let mut out = String::new();
+    for dataset in unique(records.iter().map(|r| r.dataset.as_str())) {
+        let rows: Vec<&Record> = records.iter().filter(|r| r.dataset == dataset).collect();
+        let mut values: HashMap<(&str, &str, &str), Vec<f64>> = HashMap::new();
+        for r in rows.iter() {
+            values
+                .entry((r.workload.as_str(), r.metric.as_str(), r.lib.as_str()))
+                .or_default()
+                .push(r.value);
+        }
+        let get = |workload: &str, metric: &str, lib: &str| {
+            values.get(&(workload, metric, lib)).and_then(|v| median(v))
+        };
+        let libs = unique(rows.iter().map(|r| r.lib.as_str()));
+        let workloads = unique(rows.iter().map(|r| r.workload.as_str()));
+        let has = |workload: &str, metric: &str| {
+            libs.iter()
+                .any(|lib| values.contains_key(&(workload, metric, lib)))
+        };
+        // Builds first, then the index size, then queries
+        let mut columns: Vec<(String, &str, &str)> = vec![];
+        for &w in workloads.iter().filter(|w| has(w, "build_secs")) {
+            columns.push((format!("create {}", w), w, "build_secs"));
+        }
+        let memory = workloads
+            .iter()
+            .find(|w| w.starts_with("set b") && !w.ends_with("unsorted") && has(w, "index_bytes"));
+        if let Some(&w) = memory {
+            columns.push((format!("{} size", w), w, "index_bytes"));
+        }
+        // The plain finds every lib runs, then the passes only some do
+        let (finds, rest): (Vec<&str>, Vec<&str>) = workloads
+            .iter()
+            .filter(|w| has(w, "query_secs"))
+            .partition(|w| plain_find(w));
+        for w in finds.into_iter().chain(rest) {
+            columns.push((w.to_string(), w, "query_secs"));
+        }
+        let verified = workloads.iter().any(|w| has(w, "check"));
+        let failed = |workload: &str, lib: &str| {
+            values
+                .get(&(workload, "check", lib))
+                .is_some_and(|checks| checks.contains(&0.0))
+        };
+
+        let _ = writeln!(out, "### {}\n", cell(dataset));
+        let mut header = vec![String::from("lib")];
+        header.extend(columns.iter().map(|c| cell(&c.0)));
+        if verified {
+            header.push(String::from("verify"));
+        }
+        let _ = writeln!(out, "| {} |", header.join(" | "));
+        let mut rule = vec!["---"];
+        rule.extend(columns.iter().map(|_| "---:"));
+        if verified {
+            rule.push("---");
+        }
+        let _ = writeln!(out, "| {} |", rule.join(" | "));
+
+        for &lib in libs.iter() {
+            let stopped = stopped(&rows, lib).map(|(status, _)| status);
+            let mut line = vec![cell(lib)];
+            for &(_, workload, metric) in columns.iter() {
+                let value = match get(workload, metric, lib) {
+                    Some(value) => value,
+                    None => {
+                        line.push(String::from(stopped.unwrap_or("-")));
+                        continue;
+                    }
+                };
+                if metric == "index_bytes" {
+                    line.push(bytes(value));
+                    continue;
+                }
+                // Wrong answers don't set the pace
+                let fastest = libs
+                    .iter()
+                    .filter(|l| !failed(workload, l))
+                    .filter_map(|l| get(workload, metric, l))
+                    .fold(f64::INFINITY, f64::min);
+                line.push(match fastest {
+                    // Nothing got this one right, so there's nothing to compare with
+                    f if f.is_infinite() => seconds(value),
+                    f if f == value => format!("**{} (1.00x)**", seconds(value)),
+                    f => format!("{} ({:.2}x)", seconds(value), value / f.max(1e-12)),
+                });
+            }
+            if verified {
+                let mismatched: Vec<&str> = workloads
+                    .iter()
+                    .filter(|w| failed(w, lib))
+                    .cloned()
+                    .collect();
+                line.push(if !mismatched.is_empty() {
+                    format!("MISMATCH: {}", cell(&mismatched.join(", ")))
+                } else if let Some(status) = stopped {
+                    String::from(status)
+                } else {
+                    String::from("ok")
+                });
+            }
+            let _ = writeln!(out, "| {} |", line.join(" | "));
+        }
+        out += "\n";
+    }
+    out
+}
+
+/// Bar charts of build and query time per set and workload, memory against
+/// speed, and latency histograms, all from the median over repeats.
+fn dataset_charts(html: &mut String, rows: &[&Record], colour: &dyn Fn(&str) -> &'static str) {
+    let mut values: HashMap<(&str, &str, &str), Vec<f64>> = HashMap::new();
+    for r in rows {
+        values
+            .entry((r.workload.as_str(), r.metric.as_str(), r.lib.as_str()))
+            .or_default()
+            .push(r.value);
+    }
+    let get = |workload: &str, metric: &str, lib: &str| {
+        values.get(&(workload, metric, lib)).and_then(|v| median(v))
+    };
+    let libs = unique(rows.iter().map(|r| r.lib.as_str()));
+    let workloads = unique(rows.iter().map(|r| r.workload.as_str()));
+
+    for &lib in libs.iter() {
+        if let Some((status, workload)) = stopped(rows, lib) {
+            let _ = writeln!(
+                html,
+                "<p>{} {} in {}, so its charts stop there.</p>",
+                escape(lib),
+                status,
+                escape(workload)
+            );
+        }
+    }
+
+    for &(metric, title) in [
+        ("build_secs", "Time to create"),
+        ("query_secs", "Query time"),
+    ]
+    .iter()
+    {
+        for &workload in workloads.iter() {
+            let mut bars: Vec<(&str, f64, bool)> = libs
+                .iter()
+                .filter_map(|&lib| {
+                    let value = get(workload, metric, lib)?;
+                    // Any failed check across repeats flags the lib
+                    let failed = values
+                        .get(&(workload, "check", lib))
+                        .is_some_and(|checks| checks.contains(&0.0));
+                    Some((lib, value, failed))
+                })
+                .collect();
+            if bars.is_empty() {
+                continue;
+            }
+            bars.sort_by(|a, b| a.1.total_cmp(&b.1));
+            let title = format!("{} {}", title, workload);
+            html.push_str(&bar_chart(&title, &bars, seconds, colour));
+        }
+    }
+
+    // Memory of set B's index against the time for every plain find
+    // workload, which all libs run
+    let build = workloads
+        .iter()
+        .find(|w| w.starts_with("set b") && !w.ends_with("unsorted"))
+        .cloned();
+    let points: Vec<(&str, Vec<(f64, f64)>)> = libs
+        .iter()
+        .filter_map(|&lib| {
+            let bytes = get(build?, "index_bytes", lib)?;
+            let secs: f64 = workloads
+                .iter()
+                .filter(|w| plain_find(w))
+                .filter_map(|w| get(w, "query_secs", lib))
+                .sum();
+            Some((lib, vec![(bytes, secs)]))
+        })
+        .collect();
+    if !points.is_empty() {
+        html.push_str(&plot(
+            "Memory vs speed",
+            ("set b index size", bytes),
+            ("total query time", seconds),
+            &points,
+            (false, false),
+            colour,
+        ));
+    }
+
+    // Latency histograms, as the share of sampled queries per bucket
+    for &workload in workloads.iter() {
+        let series: Vec<(&str, Vec<(f64, f64)>)> = libs
+            .iter()
+            .filter_map(|&lib| {
+                let mut buckets: Vec<(f64, f64)> = vec![];
+                for r in rows
+                    .iter()
+                    .filter(|r| r.workload == workload && r.lib == lib)
+                {
+                    if let Some(lowest) = r.metric.strip_prefix("latency_bucket_ns ") {
+                        buckets.push((lowest.parse().ok()?, r.value));
+                    }
+                }
+                if buckets.is_empty() {
+                    return None;
+                }
+                buckets.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let mut merged: Vec<(f64, f64)> = vec![];
+                for (lowest, count) in buckets {
+                    match merged.last_mut() {
+                        Some(last) if last.0 == lowest => last.1 += count,
+                        _ => merged.push((lowest.max(1.0), count)),
+                    }
+                }
+                let total: f64 = merged.iter().map(|b| b.1).sum();
+                let shares = merged.into_iter().map(|(x, c)| (x, c / total)).collect();
+                Some((lib, shares))
+            })
+            .collect();
+        if !series.is_empty() {
+            html.push_str(&plot(
+                &format!("Latency {}", workload),
+                ("query latency", |ns| seconds(ns / 1e9)),
+                ("share of queries", percent),
+                &series,
+                (true, false),
+                colour,
+            ));
+        }
+    }
+}
+
+/// Query and build cost against the number of intervals, one chart of each
+/// per density and interval size.
+fn sweep_charts(html: &mut String, table: &Table, colour: &dyn Fn(&str) -> &'static str) {
+    let col = |name: &str| table.column(name).unwrap();
+    let (n, density, min, max, lib) = (
+        col("num_intervals"),
+        col("density"),
+        col("min_interval_size"),
+        col("max_interval_size"),
+        col("lib"),
+    );
+    let (build, ns) = (col("build_secs"), col("ns_per_query"));
+    // Rows cut short, say by an interrupted sweep, are left out
+    let full: Vec<&Vec<String>> = table
+        .rows
+        .iter()
+        .filter(|r| r.len() == table.header.len())
+        .collect();
+    let groups = unique(full.iter().map(|r| (&r[density], &r[min], &r[max])));
+    for (d, lo, hi) in groups {
+        let rows: Vec<&Vec<String>> = full
+            .iter()
+            .copied()
+            .filter(|r| (&r[density], &r[min], &r[max]) == (d, lo, hi))
+            .collect();
+        let libs = unique(rows.iter().map(|r| r[lib].as_str()));
+        let number = |s: &str| s.parse::<f64>().unwrap_or(f64::NAN);
+        let series = |y: &dyn Fn(&Vec<String>) -> f64| -> Vec<(&str, Vec<(f64, f64)>)> {
+            libs.iter()
+                .map(|&l| {
+                    let points = rows
+                        .iter()
+                        .filter(|r| r[lib] == l)
+                        .map(|r| (number(&r[n]), y(r)))
+                        .collect();
+                    (l, points)
+                })
+                .collect()
+        };
+        let subtitle = format!("density {}, sizes {}..{}", d, lo, hi);
+        html.push_str(&plot(
+            &format!("Query time, {}", subtitle),
+            ("intervals", count),
+            ("per query", |ns| seconds(ns / 1e9)),
+            &series(&|r| number(&r[ns])),
+            (true, true),
+            colour,
+        ));
+        html.push_str(&plot(
+            &format!("Build time, {}", subtitle),
+            ("intervals", count),
+            ("per interval", |ns| seconds(ns / 1e9)),
+            &series(&|r| number(&r[build]) * 1e9 / number(&r[n]).max(1.0)),
+            (true, true),
+            colour,
+        ));
+    }
+}
+
+/// Horizontal bars, fastest first. Flagged bars failed `--verify`.
+fn bar_chart(
+    title: &str,
+    bars: &[(&str, f64, bool)],
+    format: fn(f64) -> String,
+    colour: &dyn Fn(&str) -> &'static str,
+) -> String {
+    let (label_width, row) = (150.0, 22.0);
+    let height = MARGIN.2 + row * bars.len() as f64 + 10.0;
+    let longest = bars.iter().map(|b| b.1).fold(0.0, f64::max).max(1e-12);
+    let span = WIDTH - label_width - 140.0;
+    let mut svg = svg_open(title, height);
+    for (i, &(lib, value, failed)) in bars.iter().enumerate() {
+        let y = MARGIN.2 + row * i as f64;
+        let width = (value / longest * span).max(1.0);
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>\
+             <rect x=\"{}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"/>\
+             <text x=\"{:.1}\" y=\"{}\"{}>{}{}</text>",
+            label_width - 6.0,
+            y + 15.0,
+            escape(lib),
+            label_width,
+            y + 3.0,
+            width,
+            row - 6.0,
+            colour(lib),
+            label_width + width + 6.0,
+            y + 15.0,
+            if failed { " fill=\"#c00\"" } else { "" },
+            format(value),
+            if failed { " MISMATCH" } else { "" }
+        );
+    }
+    svg + "</svg>\n"
+}
+
+/// Points per lib on shared axes: lines where a lib has more than one point,
+/// so a lib with one point each makes a scatter plot.
+fn plot(
+    title: &str,
+    x: (&str, fn(f64) -> String),
+    y: (&str, fn(f64) -> String),
+    series: &[(&str, Vec<(f64, f64)>)],
+    log: (bool, bool),
+    colour: &dyn Fn(&str) -> &'static str,
+) -> String {
+    let all = || series.iter().flat_map(|(_, points)| points.iter());
+    let x_axis = Axis::new(all().map(|p| p.0), log.0);
+    let y_axis = Axis::new(all().map(|p| p.1), log.1);
+    let (left, right, top, bottom) = MARGIN;
+    let (plot_w, plot_h) = (WIDTH - left - right, HEIGHT - top - bottom);
+    let px = |v: f64| left + x_axis.fraction(v) * plot_w;
+    let py = |v: f64| top + (1.0 - y_axis.fraction(v)) * plot_h;
+
+    let mut svg = svg_open(title, HEIGHT);
+    let _ = writeln!(
+        svg,
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#999\"/>",
+        left, top, plot_w, plot_h
+    );
+    for tick in x_axis.ticks() {
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{0:.1}\" x2=\"{0:.1}\" y1=\"{1}\" y2=\"{2}\" stroke=\"#eee\"/>\
+             <text x=\"{0:.1}\" y=\"{3}\" text-anchor=\"middle\">{4}</text>",
+            px(tick),
+            top,
+            top + plot_h,
+            top + plot_h + 16.0,
+            (x.1)(tick)
+        );
+    }
+    for tick in y_axis.ticks() {
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{0}\" x2=\"{1}\" y1=\"{2:.1}\" y2=\"{2:.1}\" stroke=\"#eee\"/>\
+             <text x=\"{3}\" y=\"{4:.1}\" text-anchor=\"end\">{5}</text>",
+            left,
+            left + plot_w,
+            py(tick),
+            left - 6.0,
+            py(tick) + 4.0,
+            (y.1)(tick)
+        );
+    }
+    let _ = writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\
+         <text x=\"14\" y=\"{}\" text-anchor=\"middle\" transform=\"rotate(-90 14 {})\">{}</text>",
+        left + plot_w / 2.0,
+        HEIGHT - 12.0,
+        escape(x.0),
+        top + plot_h / 2.0,
+        top + plot_h / 2.0,
+        escape(y.0)
+    );
+    for (i, (lib, points)) in series.iter().enumerate() {
+        let points: Vec<(f64, f64)> = points
+            .iter()
+            .cloned()
+            .filter(|&(a, b)| x_axis.contains(a) && y_axis.contains(b))
+            .collect();
+        let c = colour(lib);
+        if points.len() > 1 {
+            let path: Vec<String> = points
+                .iter()
+                .map(|&(a, b)| format!("{:.1},{:.1}", px(a), py(b)))
+                .collect();
+            let _ = writeln!(
+                svg,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>",
+                path.join(" "),
+                c
+            );
+        }
+        for &(a, b) in points.iter() {
+            let _ = writeln!(
+                svg,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3.5\" fill=\"{}\"><title>{}: {}, {}</title></circle>",
+                px(a),
+                py(b),
+                c,
+                escape(lib),
+                (x.1)(a),
+                (y.1)(b)
+            );
+        }
+        let ly = top + 10.0 + 18.0 * i as f64;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{}\" y=\"{}\" width=\"12\" height=\"12\" fill=\"{}\"/>\
+             <text x=\"{}\" y=\"{}\">{}</text>",
+            left + plot_w + 12.0,
+            ly - 10.0,
+            c,
+            left + plot_w + 30.0,
+            ly,
+            escape(lib)
+        );
+    }
+    svg + "</svg>\n"
+}
+
+/// Whether `workload` is a plain find pass, which every lib runs, rather
+/// than a count or seek pass only libs with native support run, or the
+/// dynamic workload.
+fn plain_find(workload: &str) -> bool {
+    !workload.starts_with("count ") && !workload.starts_with("seek ") && workload != "dynamic"
+}
+
+/// How `lib`'s run was stopped early, and in which workload, if it was.
+fn stopped<'a>(rows: &[&'a Record], lib: &str) -> Option<(&'static str, &'a str)> {
+    rows.iter().filter(|r| r.lib == lib).find_map(|r| {
+        STOPPED
+            .iter()
+            .find(|(metric, _)| r.metric == *metric)
+            .map(|(_, status)| (*status, r.workload.as_str()))
+    })
+}
+
+fn svg_open(title: &str, height: f64) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" font-size=\"12\">\n\
+         <text x=\"4\" y=\"16\" font-weight=\"bold\">{t}</text>\n",
+        w = WIDTH,
+        h = height,
+        t = escape(title)
+    )
+}
+
+/// Maps values onto `[0, 1]` along a chart side, linearly or by log10.
+struct Axis {
+    lo: f64,
+    hi: f64,
+    log: bool,
+}
+
+impl Axis {
+    fn new<I: Iterator<Item = f64>>(values: I, log: bool) -> Axis {
+        let values: Vec<f64> = values
+            .filter(|v| v.is_finite() && (!log || *v > 0.0))
+            .collect();
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        if values.is_empty() {
+            return Axis {
+                lo: if log { 1.0 } else { 0.0 },
+                hi: 10.0,
+                log,
+            };
+        }
+        if log {
+            let lo = 10f64.powf(min.log10().floor());
+            let hi = 10f64.powf(max.log10().ceil());
+            Axis {
+                lo,
+                hi: if hi > lo { hi } else { lo * 10.0 },
+                log,
+            }
+        } else {
+            let lo = min.min(0.0);
+            let hi = if max > lo { max * 1.05 } else { lo + 1.0 };
+            Axis { lo, hi, log }
+        }
+    }
+
+    fn contains(&self, v: f64) -> bool {
+        v.is_finite() && (!self.log || v > 0.0)
+    }
+
+    fn fraction(&self, v: f64) -> f64 {
+        if self.log {
+            (v.log10() - self.lo.log10()) / (self.hi.log10() - self.lo.log10())
+        } else {
+            (v - self.lo) / (self.hi - self.lo)
+        }
+    }
+
+    /// Powers of ten on a log axis, otherwise about five round steps.
+    fn ticks(&self) -> Vec<f64> {
+        if self.log {
+            let (lo, hi) = (
+                self.lo.log10().round() as i32,
+                self.hi.log10().round() as i32,
+            );
+            return (lo..=hi).map(|e| 10f64.powi(e)).collect();
+        }
+        let raw = (self.hi - self.lo) / 5.0;
+        let magnitude = 10f64.powf(raw.log10().floor());
+        let step = [1.0, 2.0, 5.0, 10.0]
+            .iter()
+            .map(|m| m * magnitude)
+            .find(|&s| s >= raw)
+            .unwrap_or(raw);
+        let mut tick = (self.lo / step).ceil() * step;
+        let mut ticks = vec![];
+        while tick <= self.hi + step * 1e-9 {
+            ticks.push(tick);
+            tick += step;
+        }
+        ticks
+    }
+}
+
+fn seconds(secs: f64) -> String {
+    if secs == 0.0 {
+        String::from("0")
+    } else if secs < 1e-6 {
+        format!("{:.3}ns", secs * 1e9)
+    } else if secs < 1e-3 {
+        format!("{:.3}µs", secs * 1e6)
+    } else if secs < 1.0 {
+        format!("{:.3}ms", secs * 1e3)
+    } else {
+        format!("{:.3}s", secs)
+    }
+}
+
+fn bytes(value: f64) -> String {
+    mem::human_bytes(value.max(0.0) as u64)
+}
+
+fn count(value: f64) -> String {
+    match value {
+        v if v >= 1e6 => format!("{}M", v / 1e6),
+        v if v >= 1e3 => format!("{}k", v / 1e3),
+        v => format!("{}", v),
+    }
+}
+
+fn percent(value: f64) -> String {
+    format!("{:.0}%", value * 100.0)
+}
+
+/// `text` made safe for a markdown table cell.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Distinct items in order of first appearance.
+fn unique<T: PartialEq, I: IntoIterator<Item = T>>(items: I) -> Vec<T> {
+    let mut result = vec![];
+    for item in items {
+        if !result.contains(&item) {
+            result.push(item);
+        }
+    }
+    result
+}
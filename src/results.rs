@@ -0,0 +1,205 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+
+/// One measurement, in the long format `--results` writes: a row per lib,
+/// workload and metric, so new metrics don't change the columns.
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub dataset: String,
+    /// Which pass over the dataset this came from, counting from 1.
+    pub repeat: usize,
+    pub lib: String,
+    /// A workload label, or for builds the set built.
+    pub workload: String,
+    pub metric: String,
+    pub value: f64,
+}
+
+pub const HEADER: &str = "dataset\trepeat\tlib\tworkload\tmetric\tvalue";
+
+struct Collector {
+    dataset: String,
+    repeat: usize,
+    records: Vec<Record>,
+    /// Where records are also written as they come, for a parent process
+    /// to read even if this one never finishes.
+    stream: Option<fs::File>,
+}
+
+thread_local! {
+    // Everything is measured from the main thread, so that's the only one
+    // that records
+    static COLLECTOR: RefCell<Collector> = const {
+        RefCell::new(Collector {
+            dataset: String::new(),
+            repeat: 1,
+            records: vec![],
+            stream: None,
+        })
+    };
+}
+
+/// Tag everything recorded from here on with `dataset` and `repeat`.
+pub fn set_context(dataset: &str, repeat: usize) {
+    COLLECTOR.with(|c| {
+        let mut c = c.borrow_mut();
+        c.dataset = dataset.to_string();
+        c.repeat = repeat;
+    });
+}
+
+/// The dataset and repeat records are being tagged with.
+pub fn context() -> (String, usize) {
+    COLLECTOR.with(|c| {
+        let c = c.borrow();
+        (c.dataset.clone(), c.repeat)
+    })
+}
+
+pub fn record(lib: &str, workload: &str, metric: &str, value: f64) {
+    COLLECTOR.with(|c| {
+        let mut c = c.borrow_mut();
+        let record = Record {
+            dataset: c.dataset.clone(),
+            repeat: c.repeat,
+            lib: lib.to_string(),
+            workload: workload.to_string(),
+            metric: metric.to_string(),
+            value,
+        };
+        if let Some(ref mut stream) = c.stream {
+            let line = format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\n",
+                record.dataset, record.repeat, lib, workload, metric, value
+            );
+            stream
+                .write_all(line.as_bytes())
+                .expect("Failed to stream results");
+        }
+        c.records.push(record);
+    });
+}
+
+/// Add records made elsewhere, such as by a child process.
+pub fn extend(records: Vec<Record>) {
+    COLLECTOR.with(|c| c.borrow_mut().records.extend(records));
+}
+
+/// Everything recorded so far.
+pub fn records() -> Vec<Record> {
+    COLLECTOR.with(|c| c.borrow().records.clone())
+}
+
+/// From here on also write every record to `path` as it is made, in the
+/// format `write` uses.
+pub fn stream_to(path: &str) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    writeln!(file, "{}", HEADER)?;
+    COLLECTOR.with(|c| c.borrow_mut().stream = Some(file));
+    Ok(())
+}
+
+/// Note in the stream, if there is one, that `label` is starting. It is a
+/// `# step` comment line, so readers of the table see it with the
+/// environment and the last one says what was running.
+pub fn step(label: &str) {
+    COLLECTOR.with(|c| {
+        if let Some(ref mut stream) = c.borrow_mut().stream {
+            writeln!(stream, "# step\t{}", label).expect("Failed to stream results");
+        }
+    });
+}
+
+/// Write `records` as a tab separated table, after the run's environment as
+/// `#` comment lines, as `sweep` does.
+pub fn write(path: &str, env: &[(String, String)], records: &[Record]) -> io::Result<()> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    for (key, value) in env {
+        writeln!(out, "# {}\t{}", key, value)?;
+    }
+    writeln!(out, "{}", HEADER)?;
+    for r in records {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            r.dataset, r.repeat, r.lib, r.workload, r.metric, r.value
+        )?;
+    }
+    Ok(())
+}
+
+/// A tab separated table as written by `write` or `sweep`: its `#` comment
+/// lines as `(key, value)`, its header, and its rows.
+pub struct Table {
+    pub env: Vec<(String, String)>,
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn read(path: &str) -> io::Result<Table> {
+        let mut table = Table {
+            env: vec![],
+            header: vec![],
+            rows: vec![],
+        };
+        for line in BufReader::new(fs::File::open(path)?).lines() {
+            let line = line?;
+            if let Some(comment) = line.strip_prefix("# ") {
+                let (key, value) = comment.split_once('\t').unwrap_or((comment, ""));
+                table.env.push((key.to_string(), value.to_string()));
+            } else if line.is_empty() {
+                continue;
+            } else if table.header.is_empty() {
+                table.header = line.split('\t').map(String::from).collect();
+            } else {
+                table
+                    .rows
+                    .push(line.split('\t').map(String::from).collect());
+            }
+        }
+        Ok(table)
+    }
+
+    /// Index of `name` in the header.
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.header.iter().position(|h| h == name)
+    }
+
+    /// The rows as records, if this is a `--results` table.
+    pub fn records(&self) -> Option<Vec<Record>> {
+        if self.header.join("\t") != HEADER {
+            return None;
+        }
+        let records = self
+            .rows
+            .iter()
+            .filter(|row| row.len() == 6)
+            .map(|row| Record {
+                dataset: row[0].clone(),
+                repeat: row[1].parse().unwrap_or(1),
+                lib: row[2].clone(),
+                workload: row[3].clone(),
+                metric: row[4].clone(),
+                value: row[5].parse().unwrap_or(f64::NAN),
+            })
+            .collect();
+        Some(records)
+    }
+}
+
+/// Median of `values`, ignoring NaNs.
+pub fn median(values: &[f64]) -> Option<f64> {
+    let mut values: Vec<f64> = values.iter().cloned().filter(|v| !v.is_nan()).collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    Some(if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
@@ -0,0 +1,159 @@
+use crate::{SortOrder, STDIN_CHILDREN, STDIN_TWICE};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+
+/// A checked in bakeoff: which datasets to build or load, what to run on
+/// them, how often, and where the results go. Lib and workload names are
+/// the ones the command line and `--help` use.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Libs to run, as for `--lib`. Empty means `All`.
+    #[serde(default)]
+    pub libs: Vec<String>,
+    /// Libs to drop from `libs`, as for `--exclude_lib`.
+    #[serde(default)]
+    pub exclude_libs: Vec<String>,
+    /// Workload keys to keep. Empty means every workload the data allows.
+    #[serde(default)]
+    pub workloads: Vec<String>,
+    #[serde(default = "one")]
+    pub repeats: usize,
+    /// As `--shuffle_order`.
+    #[serde(default)]
+    pub shuffle_order: bool,
+    /// As `--order_seed`.
+    pub order_seed: Option<u64>,
+    /// File the results are written to instead of stdout.
+    pub output: Option<String>,
+    /// As `--time_budget`, in seconds.
+    pub time_budget: Option<f64>,
+    /// As `--mem_budget`, like `"4G"`.
+    pub mem_budget: Option<String>,
+    /// As `--isolate`, `"lib"` or `"workload"`.
+    pub isolate: Option<String>,
+    #[serde(rename = "dataset")]
+    pub datasets: Vec<DatasetSpec>,
+}
+
+impl Scenario {
+    /// Check what deserializing can't, so a bad scenario fails as it loads
+    /// rather than partway through a run.
+    fn validate(&self) -> Result<(), String> {
+        let children =
+            self.isolate.is_some() || self.time_budget.is_some() || self.mem_budget.is_some();
+        for spec in self.datasets.iter() {
+            let (name, presort) = match spec {
+                DatasetSpec::Fake(spec) => (&spec.name, &spec.presort),
+                DatasetSpec::Real(spec) => (&spec.name, &spec.presort),
+            };
+            let invalid = |msg: &str| Err(format!("dataset {}: {}", name, msg));
+            if let Some(presort) = presort {
+                if SortOrder::from_name(presort).is_none() {
+                    return invalid(&format!("Unknown sort order: {}", presort));
+                }
+            }
+            if let DatasetSpec::Real(spec) = spec {
+                let stdin = [&spec.bed_a, &spec.bed_b]
+                    .iter()
+                    .filter(|path| **path == "-")
+                    .count();
+                if stdin == 2 {
+                    return invalid(STDIN_TWICE);
+                }
+                if stdin > 0 && children {
+                    return invalid(STDIN_CHILDREN);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum DatasetSpec {
+    Fake(Fake),
+    Real(Real),
+}
+
+/// Generator settings for a fake dataset. Missing fields take the same
+/// defaults as the `fake` subcommand.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fake {
+    pub name: String,
+    pub num_intervals: u32,
+    pub universe_size: u32,
+    /// A chrom.sizes file, used instead of the universe.
+    pub genome: Option<String>,
+    pub window_size: u32,
+    pub min_interval_size: u32,
+    pub max_interval_size: u32,
+    pub add_universe_spanning_interval: bool,
+    /// Fixes every random draw of the run. Without one, a seed is picked
+    /// and printed so the run can still be repeated.
+    pub seed: Option<u64>,
+    pub presort: Option<String>,
+    pub verify: bool,
+}
+
+impl Default for Fake {
+    fn default() -> Fake {
+        Fake {
+            name: String::from("fake"),
+            num_intervals: 3_000_000,
+            universe_size: 100_000_000,
+            genome: None,
+            window_size: 10_000,
+            min_interval_size: 500,
+            max_interval_size: 80_000,
+            add_universe_spanning_interval: false,
+            seed: None,
+            presort: None,
+            verify: false,
+        }
+    }
+}
+
+/// Paths for a real dataset, as given to the `real` subcommand.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Real {
+    #[serde(default = "real")]
+    pub name: String,
+    pub bed_a: String,
+    pub bed_b: String,
+    pub genome: Option<String>,
+    #[serde(default = "window_size")]
+    pub window_size: u32,
+    /// Seeds the shuffled copies made for `presort`.
+    pub seed: Option<u64>,
+    pub presort: Option<String>,
+    #[serde(default)]
+    pub verify: bool,
+}
+
+fn real() -> String {
+    String::from("real")
+}
+
+fn window_size() -> u32 {
+    Fake::default().window_size
+}
+
+/// Read a scenario, returning its text as well so it can be echoed into
+/// the results it produces.
+pub fn load(path: &str) -> io::Result<(Scenario, String)> {
+    let text = fs::read_to_string(path)?;
+    let invalid =
+        |e: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e));
+    let scenario: Scenario = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+    scenario.validate().map_err(invalid)?;
+    Ok((scenario, text))
+}
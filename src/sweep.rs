@@ -0,0 +1,125 @@
+use crate::adapters::{Adapter, Config, ContigIndex};
+use crate::Iv;
+use cpu_time::ProcessTime;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Expand a sweep spec into the values it covers: either a comma separated
+/// list, or `lo..hi/k` for `k` log-spaced values from `lo` to `hi`
+/// inclusive. Numbers may be written like `1e6`.
+pub fn parse_spec(spec: &str) -> Vec<f64> {
+    let number = |s: &str| {
+        s.trim()
+            .parse::<f64>()
+            .unwrap_or_else(|_| panic!("Bad number in sweep spec: {}", spec))
+    };
+    if let Some(range) = spec.find("..") {
+        let (lo, rest) = (&spec[..range], &spec[range + 2..]);
+        let (hi, points) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], number(&rest[slash + 1..]) as usize),
+            None => (rest, 2),
+        };
+        let (lo, hi) = (number(lo), number(hi));
+        assert!(
+            lo > 0.0 && hi >= lo && points >= 2,
+            "Sweep ranges need 0 < lo <= hi and at least 2 points: {}",
+            spec
+        );
+        let step = (hi / lo).ln() / (points - 1) as f64;
+        (0..points)
+            .map(|i| (lo.ln() + step * i as f64).exp())
+            .collect()
+    } else {
+        spec.split(',').map(number).collect()
+    }
+}
+
+/// One combination of generator parameters.
+#[derive(Clone, Copy, Debug)]
+pub struct Point {
+    pub num_intervals: u32,
+    /// Mean number of set B intervals covering a position.
+    pub density: f64,
+    pub min_interval_size: u32,
+    pub max_interval_size: u32,
+}
+
+impl Point {
+    /// The universe that gives this point its density.
+    pub fn universe_size(&self) -> u32 {
+        let mean = (self.min_interval_size as f64 + self.max_interval_size as f64) / 2.0;
+        let universe = (self.num_intervals as f64 * mean / self.density).round();
+        assert!(
+            universe < (u32::MAX - self.max_interval_size) as f64,
+            "{:?} needs a universe past u32; raise the density",
+            self
+        );
+        (universe as u32).max(1)
+    }
+}
+
+/// What one lib did at one point.
+pub struct Row {
+    pub point: Point,
+    pub lib: &'static str,
+    /// Convert and create for set B.
+    pub build_secs: f64,
+    /// Every interval of set A queried against set B.
+    pub query_secs: f64,
+    pub hits: usize,
+    pub queries: usize,
+}
+
+impl Row {
+    pub const HEADER: &'static str = "num_intervals\tuniverse_size\tdensity\tmin_interval_size\tmax_interval_size\tlib\tbuild_secs\tquery_secs\tns_per_query\thits";
+}
+
+impl fmt::Display for Row {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{:.6}\t{:.6}\t{:.1}\t{}",
+            self.point.num_intervals,
+            self.point.universe_size(),
+            self.point.density,
+            self.point.min_interval_size,
+            self.point.max_interval_size,
+            self.lib,
+            self.build_secs,
+            self.query_secs,
+            self.query_secs * 1e9 / self.queries.max(1) as f64,
+            self.hits
+        )
+    }
+}
+
+/// Build set B and query it with every interval of set A, quietly.
+pub fn measure<A: Adapter>(point: Point, set_a: &[Iv], set_b: &[Iv], config: &Config) -> Row {
+    let start = ProcessTime::now();
+    let index = ContigIndex::<A>::build(ContigIndex::<A>::convert(set_b, 1), config);
+    let build_secs = start.elapsed().as_secs_f64();
+    let start = ProcessTime::now();
+    let hits = set_a.iter().map(|iv| index.find(iv)).sum();
+    let query_secs = start.elapsed().as_secs_f64();
+    Row {
+        point,
+        lib: A::NAME,
+        build_secs,
+        query_secs,
+        hits,
+        queries: set_a.len(),
+    }
+}
+
+/// Write `rows` under a header, after the run's environment as `#` comment
+/// lines.
+pub fn write_table<W: Write>(mut out: W, env: &[(String, String)], rows: &[Row]) -> io::Result<()> {
+    for (key, value) in env {
+        writeln!(out, "# {}\t{}", key, value)?;
+    }
+    writeln!(out, "{}", Row::HEADER)?;
+    for row in rows {
+        writeln!(out, "{}", row)?;
+    }
+    Ok(())
+}
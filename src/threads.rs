@@ -0,0 +1,194 @@
+use crate::adapters::{self, Adapter, ContigIndex};
+use crate::{baselines, binning, iitree, mem, nclist, Iv};
+use crossbeam_utils::thread;
+use std::borrow::Borrow;
+use std::sync::Barrier;
+use std::time::{Duration, Instant};
+
+/// Wall clock cost of one multi-threaded pass over a query set. CPU time
+/// would add up across threads and hide any speedup.
+pub struct Timing {
+    /// Threads started, which is fewer than asked for when there are fewer
+    /// queries than that.
+    pub threads: usize,
+    /// Time for every thread to get an index it can query.
+    pub replicate: Duration,
+    pub query: Duration,
+    pub count: usize,
+}
+
+/// How query threads get at an index.
+pub trait Threaded: Adapter + Send {
+    /// Whether all threads query the one index. Libraries that aren't `Sync`
+    /// give each thread a clone of it instead.
+    const SHARED: bool = true;
+
+    /// Split `queries` evenly across `threads` and answer them all.
+    fn query_threads(index: &ContigIndex<Self>, queries: &[Iv], threads: usize) -> Timing;
+}
+
+macro_rules! shared {
+    ($($lib:ty),*) => {$(
+        impl Threaded for $lib {
+            fn query_threads(index: &ContigIndex<Self>, queries: &[Iv], threads: usize) -> Timing {
+                run_threads(queries, threads, || index)
+            }
+        }
+    )*};
+}
+
+shared!(
+    adapters::RustLapper,
+    adapters::RustHopper,
+    adapters::RustBio,
+    adapters::IProxy,
+    adapters::CoiTree,
+    adapters::AIList,
+    nclist::NCList,
+    binning::Binning,
+    iitree::IITree,
+    baselines::Naive,
+    baselines::BinarySearch
+);
+
+// The RefCell around the set makes it !Sync, so every thread gets a clone
+impl Threaded for adapters::NestedIntervals {
+    const SHARED: bool = false;
+
+    fn query_threads(index: &ContigIndex<Self>, queries: &[Iv], threads: usize) -> Timing {
+        run_threads(queries, threads, || index.clone())
+    }
+}
+
+/// Start one thread per chunk of `queries`, each querying the view `view`
+/// made for it. Every view is made before any thread starts, and threads
+/// wait for each other before querying, so making the views and querying
+/// are timed apart.
+fn run_threads<A, F, V>(queries: &[Iv], threads: usize, mut view: F) -> Timing
+where
+    A: Adapter,
+    F: FnMut() -> V,
+    V: Borrow<ContigIndex<A>> + Send,
+{
+    let chunk_size = queries.len().div_ceil(threads).max(1);
+    let chunks: Vec<&[Iv]> = queries.chunks(chunk_size).collect();
+    let start = Instant::now();
+    let views: Vec<V> = chunks.iter().map(|_| view()).collect();
+    let replicate = start.elapsed();
+    let barrier = Barrier::new(chunks.len() + 1);
+    thread::scope(|s| {
+        let handles: Vec<_> = chunks
+            .iter()
+            .zip(views)
+            .map(|(chunk, index)| {
+                let barrier = &barrier;
+                s.spawn(move |_| {
+                    barrier.wait();
+                    chunk
+                        .iter()
+                        .map(|iv| index.borrow().find(iv))
+                        .sum::<usize>()
+                })
+            })
+            .collect();
+        barrier.wait();
+        let start = Instant::now();
+        let count = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        Timing {
+            threads: chunks.len(),
+            replicate,
+            query: start.elapsed(),
+            count,
+        }
+    })
+    .unwrap()
+}
+
+/// Time `queries` on 1 to `max_threads` threads and print the curve, with
+/// speedup relative to one thread. The curve ends early once there are no
+/// queries left to give another thread, and there is none without queries.
+pub fn scaling<A: Threaded>(
+    index: &ContigIndex<A>,
+    queries: &[Iv],
+    label: &str,
+    max_threads: usize,
+) {
+    if queries.is_empty() {
+        println!("{}: {} thread scaling skipped: no queries", A::NAME, label);
+        return;
+    }
+    let mut single = None;
+    for threads in 1..=max_threads {
+        let timing = A::query_threads(index, queries, threads);
+        if threads > 1 && timing.threads < threads {
+            break;
+        }
+        let single = *single.get_or_insert(timing.query);
+        println!(
+            "{}: {} on {} threads wall time/count: {:#?}/{} ({:.2}x)",
+            A::NAME,
+            label,
+            timing.threads,
+            timing.query,
+            timing.count,
+            single.as_secs_f64() / timing.query.as_secs_f64().max(1e-9)
+        );
+        if !A::SHARED {
+            println!(
+                "{}: {} on {} threads replicas time: {:#?}",
+                A::NAME,
+                label,
+                timing.threads,
+                timing.replicate
+            );
+        }
+    }
+}
+
+/// Build `ivs` on one thread, then per contig across `threads` threads, and
+/// print the wall time and peak RSS growth of each. Conversion comes first
+/// and isn't counted, so only the builds themselves are compared.
+pub fn parallel_build<A>(
+    ivs: &[Iv],
+    num_contigs: usize,
+    config: &adapters::Config,
+    label: &str,
+    threads: usize,
+) where
+    A: Adapter + Send,
+    A::Item: Send,
+{
+    let mut single = None;
+    for &n in &[1, threads] {
+        let items = ContigIndex::<A>::convert(ivs, num_contigs);
+        let tracked = mem::reset_peak();
+        let before = mem::rss();
+        let start = Instant::now();
+        let index = if n == 1 {
+            ContigIndex::<A>::build(items, config)
+        } else {
+            ContigIndex::<A>::build_parallel(items, n, config)
+        };
+        let elapsed = start.elapsed();
+        let after = mem::rss();
+        drop(index);
+        let single = *single.get_or_insert(elapsed);
+        println!(
+            "{}: Time to create {} on {} threads wall time: {:#?} ({:.2}x)",
+            A::NAME,
+            label,
+            n,
+            elapsed,
+            single.as_secs_f64() / elapsed.as_secs_f64().max(1e-9)
+        );
+        if let (true, Some((base, _)), Some((_, peak))) = (tracked, before, after) {
+            println!(
+                "{}: Peak rss growth creating {} on {} threads: {}",
+                A::NAME,
+                label,
+                n,
+                mem::human_bytes(peak.saturating_sub(base))
+            );
+        }
+    }
+}